@@ -1,6 +1,9 @@
 use core::time::Duration;
 
-use ebus::{EbusDriver, MasterTelegram, Transmit};
+use ebus::{
+    queue::{RetryPolicy, TxQueue},
+    Clock, EbusDriver, MasterTelegram, Transmit,
+};
 
 // Depends on hardware and latency. Right value must be chosen to ensure
 // layering of the first byte after `SYN`
@@ -9,10 +12,10 @@ const ARBITRATION_DELAY: Duration = Duration::from_micros(540);
 const CRC_POLYNOM_TELEGRAM: u8 = 0x9B;
 const CRC_POLYNOM_DATA: u8 = 0x5C;
 
-fn wait_for_next_byte() -> u8 {
-    // this should await a byte from UART
+fn wait_for_next_byte() -> Option<u8> {
+    // this should await a byte from UART, with a timeout of a few ms
 
-    0x00
+    Some(0x00)
 }
 
 struct UartTxDriver;
@@ -35,89 +38,120 @@ impl Transmit for Transmitter {
     }
 }
 
-fn sleep(_d: Duration) {
-    // This function is called by the ebus driver to
-    // correctly layer its own source address with others'
-    // in order to lock the bus.
-    // It is only called with the arbitration_delay passed
-    // to `EbusDriver::new`.
+struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        // monotonic time, e.g. since boot
+        Duration::ZERO
+    }
+
+    fn delay(&self, _duration: Duration) {
+        // This function is called by the ebus driver to
+        // correctly layer its own source address with others'
+        // in order to lock the bus.
+        // It is only called with the arbitration_delay passed
+        // to `EbusDriver::new`.
+    }
 }
 
 fn poll_next_msg() -> Option<MasterTelegram> {
-    // poll your message queue here
+    // poll your application (e.g. a channel) for new messages here
     None
 }
 
 fn main() {
-    // create some sort of queue for messages to be sent,
-    // or a channel
-    // give tx to application so it can queue messages
-
     let mut uart = Transmitter(UartTxDriver);
     let mut driver = EbusDriver::new(ARBITRATION_DELAY, CRC_POLYNOM_TELEGRAM, CRC_POLYNOM_DATA, 8);
-
-    let mut msg = None;
+    driver.set_master_address(0xFF).unwrap();
+
+    // messages to be sent, ordered by priority
+    let mut queue = TxQueue::<8>::new();
+    let retry = RetryPolicy {
+        timeout: 1,
+        reply_crc_error: 1,
+    };
     loop {
         // Here, we block on the receival of a byte which is not ideal.
         // Depending on your device and architecture, you should use interrupts or
         // low latency async code.
-        msg = msg.or_else(|| poll_next_msg());
-        let byte = wait_for_next_byte();
-
-        match driver
-            .process(byte, &mut uart, sleep, msg.as_ref(), true)
+        if let Some(msg) = poll_next_msg() {
+            // keep the handle and `queue.poll` it to learn the outcome
+            if let Ok(handle) = queue.enqueue(
+                msg,
+                Some(SystemClock.now() + Duration::from_secs(10)),
+                retry,
+            ) {
+                queue.detach(handle);
+            }
+        }
+        let Some(byte) = wait_for_next_byte() else {
+            // bus is idle, let the driver handle timeouts
+            driver
+                .poll(&mut uart, &SystemClock)
+                .expect("handle uart error");
+            continue;
+        };
+
+        match queue
+            .process(&mut driver, byte, &mut uart, &SystemClock, true)
             .expect("handle uart error")
         {
             ebus::ProcessResult::None => {}
-            ebus::ProcessResult::MasterAckOk => {
+            ebus::ProcessResult::MasterAckOk { .. } => {
                 // successfully sent message with no expected reply
-                msg = None; // remove message from queue
             }
-            ebus::ProcessResult::MasterAckErr => {
-                // recipient replied ACK_ERR
-                msg = None; // remove message from queue
-                            // could also try to requeue this message for later
+            ebus::ProcessResult::MasterAckErr { .. } => {
+                // recipient replied ACK_ERR twice
+            }
+            ebus::ProcessResult::BroadcastSent => {
+                // broadcast went out, there is no acknowledge
             }
             ebus::ProcessResult::Timeout => {
-                // recipient did not reply within AUTO-SYN
-                msg = None; // remove message from queue
-                            // could also try to requeue this message for later
+                // recipient did not reply within AUTO-SYN, retried according to the policy
+            }
+            ebus::ProcessResult::InvalidSource => {
+                // message has no master address as source
             }
             ebus::ProcessResult::ReplyCrcError => {
-                // recipient sent reply but CRC check failed
-                msg = None; // remove message from queue
-                            // could also try to requeue this message for later
+                // recipient sent reply but CRC check failed, retried according to the policy
+            }
+            ebus::ProcessResult::ReplyLenOverflow { len: _ } => {
+                // recipient announced a reply longer than MAX_BUF, not retried
             }
             ebus::ProcessResult::TelegramCrcError => {
                 // some master sent telegram but CRC check failed
-                msg = None; // remove message from queue
-                            // could also try to requeue this message for later
-            }
-            ebus::ProcessResult::Request { telegram, token } => {
-                match telegram.dest {
-                    0xFF => {
-                        // this is meant for us, but master to master
-                        // schedule telegram as reply (add it to queue)
-                    }
-                    0x04 => {
-                        // this is meant for us, reply
-                        driver
-                            .reply_as_slave(&[0xDE, 0xAD, 0xBE, 0xEF], &mut uart, token)
-                            .unwrap();
-                    }
-                    _ => {
-                        // ignore
-                    }
-                }
-            }
-            ebus::ProcessResult::Reply { data: _ } => {
+            }
+            ebus::ProcessResult::Request { telegram: _, token } => {
+                // this is meant for our slave address (0x04), reply
+                driver
+                    .reply_as_slave(&[0xDE, 0xAD, 0xBE, 0xEF], &mut uart, token)
+                    .unwrap();
+            }
+            ebus::ProcessResult::Observed { telegram: _ } => {
+                // telegram for another participant, only with `set_observe(true)`
+            }
+            ebus::ProcessResult::MasterMessage { telegram: _ } => {
+                // master-master telegram for us, already acknowledged
+                // schedule an answer telegram if needed (add it to queue)
+            }
+            ebus::ProcessResult::Broadcast { telegram: _ } => {
+                // some master sent a broadcast, e.g. outdoor temperature
+            }
+            ebus::ProcessResult::Transaction { transaction: _ } => {
+                // only reported when sniffing
+            }
+            ebus::ProcessResult::VetReply { timeout_ms: _ } => {
+                // if no further byte arrives within `timeout_ms`,
+                // `driver.poll` acknowledges the reply
+            }
+            ebus::ProcessResult::Reply { .. } => {
                 // success
-                msg = None; // remove message from queue
             }
-            ebus::ProcessResult::SlaveAckOk => {
+            ebus::ProcessResult::SlaveAckOk { .. } => {
                 // our reply was acknowledged
             }
-            ebus::ProcessResult::SlaveAckErr => {
+            ebus::ProcessResult::SlaveAckErr { .. } => {
                 // our reply was not acknowledged
             }
         }
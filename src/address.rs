@@ -0,0 +1,198 @@
+use core::fmt;
+
+use crate::{BROADCAST_ADDR, ESCAPE_PREFIX, SYN};
+
+/// Nibbles a master address is made of, ordered by priority (highest first)
+const MASTER_NIBBLES: [u8; 5] = [0x0, 0x1, 0x3, 0x7, 0xF];
+
+/// Distance between a master address and its slave address
+const SLAVE_OFFSET: u8 = 5;
+
+/// eBUS address of a bus participant
+#[derive(Clone, Copy, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Address(pub u8);
+
+/// The address cannot be used where it was passed, e.g. a slave address as own master address
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct InvalidAddress(pub Address);
+
+/// What an address value is used for
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AddressKind {
+    /// One of the 25 master addresses
+    Master,
+    /// One of the 228 slave addresses
+    Slave,
+    /// Broadcast destination (0xFE)
+    Broadcast,
+    /// SYN symbol (0xAA) or escape prefix (0xA9), never used as address
+    Reserved,
+}
+
+impl Address {
+    pub const BROADCAST: Address = Address(BROADCAST_ADDR);
+
+    pub const fn kind(self) -> AddressKind {
+        if self.is_master() {
+            AddressKind::Master
+        } else if self.0 == BROADCAST_ADDR {
+            AddressKind::Broadcast
+        } else if self.is_reserved() {
+            AddressKind::Reserved
+        } else {
+            AddressKind::Slave
+        }
+    }
+
+    pub const fn is_master(self) -> bool {
+        nibble_index(self.0 & 0x0F).is_some() && nibble_index(self.0 >> 4).is_some()
+    }
+
+    pub const fn is_slave(self) -> bool {
+        matches!(self.kind(), AddressKind::Slave)
+    }
+
+    pub const fn is_broadcast(self) -> bool {
+        self.0 == BROADCAST_ADDR
+    }
+
+    /// SYN and escape prefix cannot be used as address
+    pub const fn is_reserved(self) -> bool {
+        self.0 == SYN || self.0 == ESCAPE_PREFIX
+    }
+
+    /// Whether this address may be used as destination of a telegram
+    pub const fn is_valid_dest(self) -> bool {
+        !self.is_reserved()
+    }
+
+    /// Priority class of a master (0 = highest, 4 = lowest), stored in the lower nibble
+    pub const fn priority_class(self) -> Option<u8> {
+        if self.is_master() {
+            nibble_index(self.0 & 0x0F)
+        } else {
+            None
+        }
+    }
+
+    /// Sub address of a master (0 = highest, 4 = lowest), stored in the upper nibble
+    pub const fn sub_address(self) -> Option<u8> {
+        if self.is_master() {
+            nibble_index(self.0 >> 4)
+        } else {
+            None
+        }
+    }
+
+    /// Build a master address from priority class and sub address (both `0..5`)
+    pub const fn master(priority_class: u8, sub_address: u8) -> Option<Address> {
+        if priority_class as usize >= MASTER_NIBBLES.len()
+            || sub_address as usize >= MASTER_NIBBLES.len()
+        {
+            return None;
+        }
+
+        Some(Address(
+            MASTER_NIBBLES[sub_address as usize] << 4 | MASTER_NIBBLES[priority_class as usize],
+        ))
+    }
+
+    /// Slave address belonging to this master (master + 5)
+    pub const fn slave_of_master(self) -> Option<Address> {
+        if self.is_master() {
+            Some(Address(self.0.wrapping_add(SLAVE_OFFSET)))
+        } else {
+            None
+        }
+    }
+
+    /// Master address belonging to this slave (slave - 5), if there is one
+    pub const fn master_of_slave(self) -> Option<Address> {
+        let master = Address(self.0.wrapping_sub(SLAVE_OFFSET));
+        if self.is_slave() && master.is_master() {
+            Some(master)
+        } else {
+            None
+        }
+    }
+}
+
+const fn nibble_index(nibble: u8) -> Option<u8> {
+    let mut i = 0;
+    while i < MASTER_NIBBLES.len() {
+        if MASTER_NIBBLES[i] == nibble {
+            return Some(i as u8);
+        }
+        i += 1;
+    }
+
+    None
+}
+
+impl From<u8> for Address {
+    fn from(addr: u8) -> Self {
+        Address(addr)
+    }
+}
+
+impl From<Address> for u8 {
+    fn from(addr: Address) -> Self {
+        addr.0
+    }
+}
+
+impl fmt::Debug for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Address(0x{:02X})", self.0)
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for Address {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "Address(0x{=u8:02X})", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Address, AddressKind};
+
+    #[test]
+    fn test_counts() {
+        let count = |kind| (0..=255u8).filter(|&a| Address(a).kind() == kind).count();
+
+        assert_eq!(count(AddressKind::Master), 25);
+        assert_eq!(count(AddressKind::Slave), 228);
+        assert_eq!(count(AddressKind::Broadcast), 1);
+        assert_eq!(count(AddressKind::Reserved), 2);
+    }
+
+    #[test]
+    fn test_master() {
+        let addr = Address(0x37);
+        assert!(addr.is_master());
+        assert_eq!(addr.priority_class(), Some(3));
+        assert_eq!(addr.sub_address(), Some(2));
+        assert_eq!(Address::master(3, 2), Some(addr));
+        assert_eq!(Address::master(5, 0), None);
+
+        assert_eq!(Address(0xA9).kind(), AddressKind::Reserved);
+        assert_eq!(Address(0x51).priority_class(), None);
+    }
+
+    #[test]
+    fn test_master_slave() {
+        assert_eq!(Address(0xFF).slave_of_master(), Some(Address(0x04)));
+        assert_eq!(Address(0x04).master_of_slave(), Some(Address(0xFF)));
+        assert_eq!(Address(0x10).slave_of_master(), Some(Address(0x15)));
+        assert_eq!(Address(0x15).master_of_slave(), Some(Address(0x10)));
+
+        assert_eq!(Address(0x51).master_of_slave(), None);
+        assert_eq!(Address(0x15).slave_of_master(), None);
+    }
+}
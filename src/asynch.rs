@@ -0,0 +1,317 @@
+//! Async driver on top of [`embedded_io_async`] (feature `async`).
+
+use core::{
+    future::{poll_fn, Future},
+    pin::pin,
+    task::Poll,
+};
+
+use embedded_hal_async::delay::DelayNs;
+use embedded_io_async::{Read, Write};
+
+pub use crate::Outcome;
+use crate::{
+    clock::DeferredDelay, Clock, EbusDriver, MasterTelegram, ProcessResult, RequestToken,
+    SendError, Telegram, Transmit, AUTO_SYN_POLL,
+};
+
+/// Enough for a telegram of MAX_BUF bytes with every byte escaped
+const TX_BUF: usize = 2 * (crate::MAX_BUF + 8);
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// UART error
+    Io(E),
+    /// UART returned no more data
+    Closed,
+    /// Recipient did not acknowledge
+    Nack { attempts: u8 },
+    /// Recipient did not answer before AUTO-SYN
+    Timeout,
+    /// Reply CRC was wrong twice
+    ReplyCrcError,
+    /// Slave announced a reply longer than `MAX_BUF`
+    ReplyLenOverflow,
+    /// Telegram has no valid master source address
+    InvalidSource,
+    /// More bytes to transmit than fit into the transmit buffer
+    TxOverflow,
+}
+
+impl<E> From<SendError> for Error<E> {
+    fn from(e: SendError) -> Self {
+        match e {
+            SendError::Nack { attempts } => Error::Nack { attempts },
+            SendError::Timeout => Error::Timeout,
+            SendError::ReplyCrcError => Error::ReplyCrcError,
+            SendError::ReplyLenOverflow => Error::ReplyLenOverflow,
+            SendError::InvalidSource => Error::InvalidSource,
+        }
+    }
+}
+
+/// Master-slave request addressed to us, answer with [`AsyncEbus::reply`]
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Request {
+    pub telegram: Telegram,
+    token: RequestToken,
+}
+
+/// What [`AsyncEbus::run`] stopped for
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Event {
+    /// The telegram passed to `run` has been sent
+    Sent(Outcome),
+    /// Master-slave request addressed to us, answer it with [`AsyncEbus::reply`] before
+    /// calling `run` again
+    Request(Request),
+}
+
+/// Owns UART, delay and clock and runs the receive loop of an [`EbusDriver`]
+pub struct AsyncEbus<U, D, C> {
+    driver: EbusDriver,
+    uart: U,
+    delay: D,
+    /// Only its time is used, the arbitration delay is awaited with `delay`
+    clock: DeferredDelay<C>,
+    tx: TxBuffer,
+}
+
+impl<U: Read + Write, D: DelayNs, C: Clock> AsyncEbus<U, D, C> {
+    pub fn new(driver: EbusDriver, uart: U, delay: D, clock: C) -> Self {
+        AsyncEbus {
+            driver,
+            uart,
+            delay,
+            clock: DeferredDelay::new(clock),
+            tx: TxBuffer::default(),
+        }
+    }
+
+    pub fn driver(&mut self) -> &mut EbusDriver {
+        &mut self.driver
+    }
+
+    pub fn into_inner(self) -> (EbusDriver, U, D, C) {
+        (self.driver, self.uart, self.delay, self.clock.clock)
+    }
+
+    /// Run the bus until `msg` has been sent or a request addressed to us arrives.
+    ///
+    /// Without `msg`, only returns for requests. Failing to send `msg` is reported as error,
+    /// like with [`AsyncEbus::send`]. After a request, call `run` again with the same `msg`
+    /// to continue sending it.
+    pub async fn run(&mut self, msg: Option<&MasterTelegram>) -> Result<Event, Error<U::Error>> {
+        loop {
+            match self.step(msg).await? {
+                ProcessResult::Request { telegram, token } => {
+                    return Ok(Event::Request(Request { telegram, token }));
+                }
+                res if msg.is_some() => {
+                    if let Some(res) = res.outcome() {
+                        return res.map(Event::Sent).map_err(Error::from);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Send a telegram and wait for its outcome.
+    ///
+    /// Requests received in the meantime are left unanswered, use [`AsyncEbus::run`] if the
+    /// device has to answer them.
+    pub async fn send(&mut self, msg: &MasterTelegram) -> Result<Outcome, Error<U::Error>> {
+        loop {
+            if let Event::Sent(outcome) = self.run(Some(msg)).await? {
+                return Ok(outcome);
+            }
+        }
+    }
+
+    /// Wait for the next master-slave request addressed to us
+    pub async fn next_request(&mut self) -> Result<Request, Error<U::Error>> {
+        loop {
+            if let Event::Request(request) = self.run(None).await? {
+                return Ok(request);
+            }
+        }
+    }
+
+    /// Answer a request, this has to happen right after receiving it
+    pub async fn reply(&mut self, request: Request, data: &[u8]) -> Result<(), Error<U::Error>> {
+        self.driver
+            .reply_as_slave(data, &mut self.tx, request.token)
+            .map_err(|_| Error::TxOverflow)?;
+        self.flush().await
+    }
+
+    /// Read and process one byte
+    async fn step(
+        &mut self,
+        msg: Option<&MasterTelegram>,
+    ) -> Result<ProcessResult, Error<U::Error>> {
+        let word = self.read_byte().await?;
+        let res = self
+            .driver
+            .process(word, &mut self.tx, &self.clock, msg, true)
+            .map_err(|_| Error::TxOverflow)?;
+
+        if let Some(delay) = self.clock.delay.take() {
+            self.delay.delay_us(delay.as_micros() as u32).await;
+        }
+        self.flush().await?;
+
+        if let ProcessResult::VetReply { timeout_ms } = res {
+            return self.vet(msg, timeout_ms).await;
+        }
+
+        Ok(res)
+    }
+
+    /// Wait for more bytes, acknowledge the reply if there are none
+    async fn vet(
+        &mut self,
+        msg: Option<&MasterTelegram>,
+        timeout_ms: u16,
+    ) -> Result<ProcessResult, Error<U::Error>> {
+        let mut byte = [0];
+        let timeout = self.delay.delay_ms(timeout_ms as u32);
+        let word = match select(self.uart.read(&mut byte), timeout).await {
+            Either::Left(Ok(0)) => return Err(Error::Closed),
+            Either::Left(Ok(_)) => byte[0],
+            Either::Left(Err(e)) => return Err(Error::Io(e)),
+            Either::Right(()) => {
+                self.driver
+                    .vet_timeout(&mut self.tx)
+                    .map_err(|_| Error::TxOverflow)?;
+                self.flush().await?;
+                // our own ACK comes back
+                self.read_byte().await?
+            }
+        };
+
+        let res = self
+            .driver
+            .process(word, &mut self.tx, &self.clock, msg, true)
+            .map_err(|_| Error::TxOverflow)?;
+        self.flush().await?;
+
+        Ok(res)
+    }
+
+    async fn read_byte(&mut self) -> Result<u8, Error<U::Error>> {
+        let mut byte = [0];
+        if !self.driver.is_auto_syn_enabled() {
+            return match self.uart.read(&mut byte).await {
+                Ok(0) => Err(Error::Closed),
+                Ok(_) => Ok(byte[0]),
+                Err(e) => Err(Error::Io(e)),
+            };
+        }
+
+        // wake up regularly to act as AUTO-SYN generator
+        loop {
+            let poll = self.delay.delay_us(AUTO_SYN_POLL.as_micros() as u32);
+            match select(self.uart.read(&mut byte), poll).await {
+                Either::Left(Ok(0)) => return Err(Error::Closed),
+                Either::Left(Ok(_)) => return Ok(byte[0]),
+                Either::Left(Err(e)) => return Err(Error::Io(e)),
+                Either::Right(()) => {
+                    self.driver
+                        .poll(&mut self.tx, &self.clock)
+                        .map_err(|_| Error::TxOverflow)?;
+                    self.flush().await?;
+                }
+            }
+        }
+    }
+
+    async fn flush(&mut self) -> Result<(), Error<U::Error>> {
+        if !self.tx.as_bytes().is_empty() {
+            self.uart
+                .write_all(self.tx.as_bytes())
+                .await
+                .map_err(Error::Io)?;
+            self.tx.clear();
+        }
+
+        Ok(())
+    }
+}
+
+/// Collects bytes from the driver so they can be written asynchronously
+struct TxBuffer {
+    buf: [u8; TX_BUF],
+    len: usize,
+}
+
+impl Default for TxBuffer {
+    fn default() -> Self {
+        TxBuffer {
+            buf: [0; TX_BUF],
+            len: 0,
+        }
+    }
+}
+
+impl TxBuffer {
+    fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+
+    fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+/// The driver wants to send more than fits into a telegram
+#[derive(Debug)]
+struct Overflow;
+
+impl Transmit for TxBuffer {
+    type Error = Overflow;
+
+    fn transmit_raw(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        let buf = self
+            .buf
+            .get_mut(self.len..self.len + bytes.len())
+            .ok_or(Overflow)?;
+        buf.copy_from_slice(bytes);
+        self.len += bytes.len();
+
+        Ok(())
+    }
+
+    fn clear_buffer(&mut self) -> Result<(), Self::Error> {
+        self.clear();
+
+        Ok(())
+    }
+}
+
+enum Either<A, B> {
+    Left(A),
+    Right(B),
+}
+
+/// Wait for the first of two futures, the other one is dropped
+async fn select<A: Future, B: Future>(a: A, b: B) -> Either<A::Output, B::Output> {
+    let mut a = pin!(a);
+    let mut b = pin!(b);
+
+    poll_fn(|cx| {
+        if let Poll::Ready(x) = a.as_mut().poll(cx) {
+            return Poll::Ready(Either::Left(x));
+        }
+        if let Poll::Ready(x) = b.as_mut().poll(cx) {
+            return Poll::Ready(Either::Right(x));
+        }
+
+        Poll::Pending
+    })
+    .await
+}
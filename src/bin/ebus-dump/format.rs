@@ -0,0 +1,147 @@
+//! Input parsing and output formatting.
+
+use std::fmt::Write;
+
+use ebus::{Address, Transaction};
+
+const RESET: &str = "\x1b[0m";
+const CYAN: &str = "\x1b[36m";
+const GREEN: &str = "\x1b[32m";
+const RED: &str = "\x1b[31m";
+const YELLOW: &str = "\x1b[33m";
+
+/// Parse a hex capture as pasted from somewhere else.
+///
+/// Bytes are separated by whitespace or commas, or written without separator (`AA1008`);
+/// `0x` prefixes are ignored.
+pub fn parse_hex(text: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = vec![];
+    for token in text.split(|c: char| c.is_whitespace() || c == ',') {
+        let token = token.trim_start_matches("0x").trim_start_matches("0X");
+        if !token.is_ascii() || !token.len().is_multiple_of(2) {
+            return Err(format!("invalid hex: {token}"));
+        }
+        for hex in token.as_bytes().chunks(2) {
+            let hex = std::str::from_utf8(hex).unwrap();
+            bytes.push(u8::from_str_radix(hex, 16).map_err(|_| format!("invalid hex: {token}"))?);
+        }
+    }
+
+    Ok(bytes)
+}
+
+/// One line per transaction, e.g.
+/// `10 -> 08 B509 0D0600 crc ok, ACK, reply 4243 crc ok, ACK`
+pub fn format_transaction(transaction: &Transaction, color: bool) -> String {
+    let paint = |s: &str, code: &str| {
+        if color {
+            format!("{code}{s}{RESET}")
+        } else {
+            s.into()
+        }
+    };
+    let crc = |ok: bool| {
+        if ok {
+            paint("crc ok", GREEN)
+        } else {
+            paint("crc error", RED)
+        }
+    };
+    let ack = |ack: Option<u8>| match ack {
+        Some(0x00) => paint("ACK", GREEN),
+        Some(0xFF) => paint("NACK", RED),
+        Some(other) => paint(&format!("ACK? {other:02X}"), RED),
+        None => paint("no ACK", YELLOW),
+    };
+
+    let telegram = &transaction.telegram;
+    let mut line = format!(
+        "{} -> {} {}",
+        paint(&format!("{:02X}", telegram.src), CYAN),
+        paint(&format!("{:02X}", telegram.dest), CYAN),
+        paint(&format!("{:04X}", telegram.service), YELLOW),
+    );
+    if !telegram.data.as_bytes().is_empty() {
+        write!(line, " {}", hex(telegram.data.as_bytes())).unwrap();
+    }
+    write!(line, " {}", crc(transaction.telegram_crc_ok)).unwrap();
+
+    if Address(telegram.dest).is_broadcast() {
+        write!(line, ", broadcast").unwrap();
+        return line;
+    }
+    write!(line, ", {}", ack(transaction.slave_ack)).unwrap();
+    if let Some(reply) = &transaction.reply {
+        let reply = match reply.as_bytes() {
+            [] => "-".into(),
+            bytes => hex(bytes),
+        };
+        write!(
+            line,
+            ", reply {reply} {}, {}",
+            crc(transaction.reply_crc_ok == Some(true)),
+            ack(transaction.master_ack)
+        )
+        .unwrap();
+    }
+
+    line
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02X}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use ebus::{Buffer, Telegram, Transaction};
+
+    use super::{format_transaction, parse_hex};
+
+    fn transaction() -> Transaction {
+        Transaction {
+            telegram: Telegram {
+                src: 0x10,
+                dest: 0x08,
+                service: 0xB509,
+                data: Buffer::from_slice(&[0x0D, 0x06, 0x00]),
+            },
+            telegram_crc_ok: true,
+            slave_ack: Some(0x00),
+            reply: Some(Buffer::from_slice(&[0x42, 0x43])),
+            reply_crc_ok: Some(false),
+            master_ack: Some(0xFF),
+        }
+    }
+
+    #[test]
+    fn test_parse_hex() {
+        assert_eq!(
+            parse_hex("aa 10 08,0xb5\n09"),
+            Ok(vec![0xAA, 0x10, 0x08, 0xB5, 0x09])
+        );
+        assert_eq!(parse_hex("AA1008"), Ok(vec![0xAA, 0x10, 0x08]));
+        assert_eq!(parse_hex("  "), Ok(vec![]));
+        assert!(parse_hex("AA1").is_err());
+        assert!(parse_hex("zz").is_err());
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(
+            format_transaction(&transaction(), false),
+            "10 -> 08 B509 0D0600 crc ok, ACK, reply 4243 crc error, NACK"
+        );
+
+        let mut broadcast = transaction();
+        broadcast.telegram.dest = 0xFE;
+        broadcast.telegram_crc_ok = false;
+        assert_eq!(
+            format_transaction(&broadcast, false),
+            "10 -> FE B509 0D0600 crc error, broadcast"
+        );
+
+        let colored = format_transaction(&transaction(), true);
+        assert!(colored.contains("\x1b[31mNACK\x1b[0m"));
+    }
+}
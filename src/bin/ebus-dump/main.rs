@@ -0,0 +1,238 @@
+//! Prints the transactions in raw eBUS bytes, one per line (feature `dump`).
+//!
+//! ```text
+//! echo "AA 10 08 B5 09 03 0D 06 00 ..." | ebus-dump --hex
+//! ebus-dump capture.bin
+//! ebus-dump --json /dev/ttyUSB0
+//! ```
+
+mod format;
+
+use std::{
+    cell::Cell,
+    convert::Infallible,
+    fs::File,
+    io::{self, IsTerminal, Read, Write},
+    process::ExitCode,
+    time::{Duration, Instant},
+};
+
+use ebus::{Clock, EbusDriver, ProcessResult, StdClock, Transaction, Transmit};
+
+const USAGE: &str = "\
+usage: ebus-dump [--hex] [--json] [--color WHEN] [-b BAUD] [FILE | DEVICE]
+
+Reads raw eBUS bytes from FILE, a serial DEVICE (/dev/...) or stdin (no argument or `-`)
+and prints one line per transaction.
+
+ --hex         input is text with hex bytes, e.g. `AA 10 08 B5 09`
+ --json        print transactions as JSON, one object per line
+ --color WHEN  always, never or auto (default, if stdout is a terminal)
+ -b BAUD       baud rate of DEVICE (default 2400)";
+
+/// Duration of one byte at 2400 baud
+const BYTE_TIME: Duration = Duration::from_micros(4167);
+
+struct Args {
+    input: Option<String>,
+    hex: bool,
+    json: bool,
+    color: bool,
+    baud: u32,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = Args {
+            input: None,
+            hex: false,
+            json: false,
+            color: io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+            baud: 2400,
+        };
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("missing value for {arg}"));
+            match arg.as_str() {
+                "--hex" => parsed.hex = true,
+                "--json" => parsed.json = true,
+                "--color" => match value()?.as_str() {
+                    "always" => parsed.color = true,
+                    "never" => parsed.color = false,
+                    "auto" => {}
+                    other => return Err(format!("invalid value for --color: {other}")),
+                },
+                "-b" => {
+                    let value = value()?;
+                    parsed.baud = value
+                        .parse()
+                        .map_err(|_| format!("invalid baud rate {value}"))?;
+                }
+                "-" => parsed.input = None,
+                arg if arg.starts_with('-') => return Err(format!("unknown argument {arg}")),
+                _ if parsed.input.is_some() => return Err("more than one input".into()),
+                _ => parsed.input = Some(arg),
+            }
+        }
+
+        Ok(parsed)
+    }
+}
+
+/// Advances by the duration of a byte with every call, for captures without timing
+#[derive(Default)]
+struct ByteClock(Cell<Duration>);
+
+impl Clock for ByteClock {
+    fn now(&self) -> Duration {
+        let now = self.0.get();
+        self.0.set(now + BYTE_TIME);
+
+        now
+    }
+
+    fn delay(&self, _duration: Duration) {}
+}
+
+/// The driver never transmits while sniffing
+struct NoTransmit;
+
+impl Transmit for NoTransmit {
+    type Error = Infallible;
+
+    fn transmit_raw(&mut self, _bytes: &[u8]) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn clear_buffer(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+struct Dump {
+    driver: EbusDriver,
+    json: bool,
+    color: bool,
+    /// Set when reading from a device
+    start: Option<Instant>,
+    out: io::StdoutLock<'static>,
+}
+
+impl Dump {
+    fn feed(&mut self, bytes: &[u8], clock: &impl Clock) -> io::Result<()> {
+        for &word in bytes {
+            let Ok(res) = self
+                .driver
+                .process(word, &mut NoTransmit, clock, None, false);
+            if let ProcessResult::Transaction { transaction } = res {
+                self.print(&transaction)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn print(&mut self, transaction: &Transaction) -> io::Result<()> {
+        let time = self.start.map(|start| start.elapsed().as_secs_f64());
+        if self.json {
+            let mut line = serde_json::to_value(transaction)?;
+            // seconds since the start, only when reading from a device
+            if let Some(time) = time {
+                line["time"] = time.into();
+            }
+            serde_json::to_writer(&mut self.out, &line)?;
+            writeln!(self.out)?;
+        } else {
+            if let Some(time) = time {
+                write!(self.out, "{time:10.3} ")?;
+            }
+            writeln!(
+                self.out,
+                "{}",
+                format::format_transaction(transaction, self.color)
+            )?;
+        }
+
+        self.out.flush()
+    }
+}
+
+fn main() -> ExitCode {
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) if e == "unknown argument --help" || e == "unknown argument -h" => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("{e}\n\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        // e.g. piped into `head`
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("ebus-dump: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: Args) -> io::Result<()> {
+    let mut driver = EbusDriver::new(Duration::ZERO, 0x9B, 0x5C, 0);
+    driver.set_sniffing(true);
+    let mut dump = Dump {
+        driver,
+        json: args.json,
+        color: args.color,
+        start: None,
+        out: io::stdout().lock(),
+    };
+
+    let input: Box<dyn Read> = match &args.input {
+        Some(path) if path.starts_with("/dev/") && !args.hex => {
+            let port = serialport::new(path, args.baud)
+                .timeout(Duration::from_secs(1))
+                .open()?;
+            return follow(&mut dump, port);
+        }
+        Some(path) => Box::new(File::open(path)?),
+        None => Box::new(io::stdin().lock()),
+    };
+
+    let mut bytes = vec![];
+    let mut input = input;
+    if args.hex {
+        let mut text = String::new();
+        input.read_to_string(&mut text)?;
+        bytes =
+            format::parse_hex(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    } else {
+        input.read_to_end(&mut bytes)?;
+    }
+    // the last transaction is only complete with the SYN after it
+    if bytes.last().is_some_and(|&byte| byte != 0xAA) {
+        bytes.push(0xAA);
+    }
+
+    dump.feed(&bytes, &ByteClock::default())
+}
+
+/// Dump a serial device until it is closed
+fn follow(dump: &mut Dump, mut port: impl Read) -> io::Result<()> {
+    let clock = StdClock::default();
+    dump.start = Some(Instant::now());
+
+    let mut buf = [0; 64];
+    loop {
+        match port.read(&mut buf) {
+            Ok(0) => return Ok(()),
+            Ok(n) => dump.feed(&buf[..n], &clock)?,
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
@@ -0,0 +1,425 @@
+//! Commands of the ebusd TCP protocol, as far as we support them.
+
+use core::fmt;
+
+use ebus::{
+    service::Identification, Address, Buffer, MasterTelegram, Outcome, SendError, Telegram,
+    TelegramFlag, TelegramFlags, Transaction, MAX_BUF,
+};
+
+/// A line received from a client
+#[derive(Clone, Debug, PartialEq)]
+pub enum Request {
+    /// `hex`, `read -h` or `write -h`, sent with `src` or our own address
+    Hex {
+        src: Option<u8>,
+        telegram: Telegram,
+    },
+    /// Scan every slave address in the background
+    ScanFull,
+    /// Scan one slave address and wait for the answer
+    ScanOne {
+        dest: u8,
+    },
+    /// Print the devices found so far
+    ScanResult,
+    Listen,
+    ListenStop,
+    Info,
+    Help,
+    Quit,
+}
+
+/// Errors reported to the client, formatted like ebusd does
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Error {
+    CommandNotFound,
+    InvalidArgument,
+    InvalidAddress,
+    InvalidLength,
+    /// Reply longer than announced or supported
+    InvalidPosition,
+    /// Named messages need message definitions, which we do not have
+    ElementNotFound,
+    Nack,
+    Timeout,
+    Crc,
+    NoSignal,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Error::CommandNotFound => "ERR: command not found",
+            Error::InvalidArgument => "ERR: invalid argument",
+            Error::InvalidAddress => "ERR: invalid address",
+            Error::InvalidLength => "ERR: invalid numeric argument",
+            Error::InvalidPosition => "ERR: invalid position",
+            Error::ElementNotFound => "ERR: element not found",
+            Error::Nack => "ERR: NAK received",
+            Error::Timeout => "ERR: read timeout",
+            Error::Crc => "ERR: CRC error",
+            Error::NoSignal => "ERR: no signal",
+        })
+    }
+}
+
+impl From<SendError> for Error {
+    fn from(e: SendError) -> Self {
+        match e {
+            SendError::Nack { .. } => Error::Nack,
+            SendError::Timeout => Error::Timeout,
+            SendError::ReplyCrcError => Error::Crc,
+            SendError::ReplyLenOverflow => Error::InvalidPosition,
+            SendError::InvalidSource => Error::InvalidAddress,
+        }
+    }
+}
+
+pub const HELP: &str = "\
+commands:
+ hex [-s QQ] ZZPBSBNNDD    send a telegram, prints the reply as NNDD
+ read -h [-s QQ] ZZPBSBNNDD
+ write -h [-s QQ] ZZPBSBNNDD
+ scan [full]               scan all slave addresses in the background
+ scan ZZ                   scan a single slave address
+ scan result               list the devices found
+ listen [stop]             stream the traffic on the bus
+ info                      show information about the bus
+ quit                      close the connection";
+
+impl Request {
+    pub fn parse(line: &str) -> Result<Self, Error> {
+        let mut args = line.split_whitespace();
+        let Some(command) = args.next() else {
+            return Err(Error::CommandNotFound);
+        };
+        let args: Vec<_> = args.collect();
+
+        match command.to_ascii_lowercase().as_str() {
+            "hex" => parse_hex_args(&args, true),
+            "read" | "r" | "write" | "w" => parse_hex_args(&args, false),
+            "scan" => match args[..] {
+                [] | ["full"] => Ok(Request::ScanFull),
+                ["result"] => Ok(Request::ScanResult),
+                [dest] => {
+                    let dest = parse_addr(dest)?;
+                    if !Address(dest).is_slave() {
+                        return Err(Error::InvalidAddress);
+                    }
+                    Ok(Request::ScanOne { dest })
+                }
+                _ => Err(Error::InvalidArgument),
+            },
+            "listen" | "l" => match args[..] {
+                [] => Ok(Request::Listen),
+                ["stop"] => Ok(Request::ListenStop),
+                _ => Err(Error::InvalidArgument),
+            },
+            "info" | "i" => Ok(Request::Info),
+            "help" | "?" => Ok(Request::Help),
+            "quit" | "q" => Ok(Request::Quit),
+            _ => Err(Error::CommandNotFound),
+        }
+    }
+}
+
+/// Options of `hex`, `read` and `write`; the latter two only support raw telegrams with `-h`
+fn parse_hex_args(args: &[&str], mut hex: bool) -> Result<Request, Error> {
+    let mut src = None;
+    let mut positional = vec![];
+    let mut args = args.iter();
+    while let Some(&arg) = args.next() {
+        match arg {
+            "-h" => hex = true,
+            "-s" => {
+                let addr = parse_addr(args.next().ok_or(Error::InvalidArgument)?)?;
+                if !Address(addr).is_master() {
+                    return Err(Error::InvalidAddress);
+                }
+                src = Some(addr);
+            }
+            // other options only matter for named messages
+            arg if arg.starts_with('-') => {}
+            arg => positional.push(arg),
+        }
+    }
+
+    match positional[..] {
+        [telegram] if hex => Ok(Request::Hex {
+            src,
+            telegram: parse_telegram(telegram)?,
+        }),
+        _ if hex => Err(Error::InvalidArgument),
+        _ => Err(Error::ElementNotFound),
+    }
+}
+
+fn parse_addr(s: &str) -> Result<u8, Error> {
+    match parse_bytes(s)?[..] {
+        [addr] => Ok(addr),
+        _ => Err(Error::InvalidAddress),
+    }
+}
+
+fn parse_bytes(s: &str) -> Result<Vec<u8>, Error> {
+    if !s.len().is_multiple_of(2) || !s.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return Err(Error::InvalidArgument);
+    }
+
+    Ok(s.as_bytes()
+        .chunks(2)
+        .map(|hex| u8::from_str_radix(core::str::from_utf8(hex).unwrap(), 16).unwrap())
+        .collect())
+}
+
+/// `ZZPBSBNNDD...`, the source is filled in when sending
+fn parse_telegram(s: &str) -> Result<Telegram, Error> {
+    let bytes = parse_bytes(s)?;
+    let [dest, pb, sb, len, data @ ..] = &bytes[..] else {
+        return Err(Error::InvalidArgument);
+    };
+    if *len as usize != data.len() || data.len() > MAX_BUF {
+        return Err(Error::InvalidLength);
+    }
+    if Address(*dest).is_reserved() {
+        return Err(Error::InvalidAddress);
+    }
+
+    Ok(Telegram {
+        src: 0,
+        dest: *dest,
+        service: u16::from_be_bytes([*pb, *sb]),
+        data: Buffer::from_slice(data),
+    })
+}
+
+/// Only master-slave telegrams are answered
+pub fn master_telegram(src: u8, mut telegram: Telegram) -> MasterTelegram {
+    telegram.src = src;
+    let flags = if Address(telegram.dest).is_slave() {
+        TelegramFlag::ExpectReply | TelegramFlags::none()
+    } else {
+        TelegramFlags::none()
+    };
+
+    MasterTelegram { telegram, flags }
+}
+
+/// Answer to `hex`: the reply as `NNDD...` or `done`
+pub fn format_outcome(outcome: &Outcome) -> String {
+    match outcome {
+        Outcome::Reply { data, .. } => {
+            let data = data.as_bytes();
+            format!("{:02x}{}", data.len(), hex(data))
+        }
+        Outcome::Acked { .. } | Outcome::BroadcastSent => "done".into(),
+    }
+}
+
+/// `QQZZPBSBNNDD...`
+pub fn format_telegram(telegram: &Telegram) -> String {
+    let data = telegram.data.as_bytes();
+    let [pb, sb] = telegram.service.to_be_bytes();
+
+    format!(
+        "{}{:02x}{}",
+        hex(&[telegram.src, telegram.dest, pb, sb]),
+        data.len(),
+        hex(data)
+    )
+}
+
+/// `QQZZPBSBNNDD... / NNDD...` with the answer of the recipient, as printed by `listen`.
+///
+/// The answer is left out for broadcasts and acknowledged master-master telegrams, errors
+/// are printed instead of it.
+pub fn format_transaction(transaction: &Transaction) -> String {
+    let telegram = format_telegram(&transaction.telegram);
+    let dest = Address(transaction.telegram.dest);
+    let answer = if !transaction.telegram_crc_ok {
+        Err(Error::Crc)
+    } else if dest.is_broadcast() {
+        return telegram;
+    } else {
+        match (transaction.slave_ack, &transaction.reply) {
+            (None, _) => Err(Error::Timeout),
+            (Some(0x00), None) if dest.is_master() => return telegram,
+            (Some(0x00), None) => Err(Error::Timeout),
+            (Some(0x00), Some(_)) if transaction.reply_crc_ok != Some(true) => Err(Error::Crc),
+            (Some(0x00), Some(reply)) => {
+                let reply = reply.as_bytes();
+                Ok(format!("{:02x}{}", reply.len(), hex(reply)))
+            }
+            (Some(_), _) => Err(Error::Nack),
+        }
+    };
+
+    match answer {
+        Ok(reply) => format!("{telegram} / {reply}"),
+        Err(e) => format!("{telegram} / {e}"),
+    }
+}
+
+/// One line of `scan result`: `ZZ;manufacturer;id;sw;hw`
+pub fn format_identification(addr: u8, id: &Identification) -> String {
+    format!(
+        "{addr:02x};{:02x};{};{:02}{:02};{:02}{:02}",
+        id.manufacturer,
+        id.device_id_str().unwrap_or_default(),
+        id.sw_version.major,
+        id.sw_version.minor,
+        id.hw_version.major,
+        id.hw_version.minor,
+    )
+}
+
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use ebus::{
+        service::{Identification, Version},
+        Buffer, Outcome, Telegram, TelegramFlag, Transaction,
+    };
+
+    use super::{
+        format_identification, format_outcome, format_transaction, master_telegram, Error, Request,
+    };
+
+    #[test]
+    fn test_parse_hex() {
+        let telegram = Telegram {
+            src: 0,
+            dest: 0x08,
+            service: 0xB509,
+            data: Buffer::from_slice(&[0x0D, 0x06, 0x00]),
+        };
+        assert_eq!(
+            Request::parse("hex 08b509030d0600"),
+            Ok(Request::Hex {
+                src: None,
+                telegram: telegram.clone(),
+            })
+        );
+        assert_eq!(
+            Request::parse("read -h -s 31 08B509030D0600"),
+            Ok(Request::Hex {
+                src: Some(0x31),
+                telegram,
+            })
+        );
+
+        assert_eq!(Request::parse("hex 08b50903"), Err(Error::InvalidLength));
+        assert_eq!(Request::parse("hex 08b5090"), Err(Error::InvalidArgument));
+        assert_eq!(
+            Request::parse("hex -s 08 08b50900"),
+            Err(Error::InvalidAddress)
+        );
+        assert_eq!(Request::parse("hex aab50900"), Err(Error::InvalidAddress));
+        assert_eq!(
+            Request::parse("read -c bai FlowTemp"),
+            Err(Error::ElementNotFound)
+        );
+    }
+
+    #[test]
+    fn test_parse_other() {
+        assert_eq!(Request::parse("scan"), Ok(Request::ScanFull));
+        assert_eq!(Request::parse("scan result"), Ok(Request::ScanResult));
+        assert_eq!(
+            Request::parse("scan 08"),
+            Ok(Request::ScanOne { dest: 0x08 })
+        );
+        assert_eq!(Request::parse("scan 10"), Err(Error::InvalidAddress));
+        assert_eq!(Request::parse("LISTEN stop"), Ok(Request::ListenStop));
+        assert_eq!(Request::parse("frobnicate"), Err(Error::CommandNotFound));
+        assert_eq!(Request::parse(""), Err(Error::CommandNotFound));
+    }
+
+    #[test]
+    fn test_master_telegram() {
+        let Ok(Request::Hex { telegram, .. }) = Request::parse("hex 08070400") else {
+            unreachable!()
+        };
+        let msg = master_telegram(0x31, telegram.clone());
+        assert_eq!(msg.telegram.src, 0x31);
+        assert!(msg.flags & TelegramFlag::ExpectReply);
+
+        let msg = master_telegram(
+            0x31,
+            Telegram {
+                dest: 0xFE,
+                ..telegram
+            },
+        );
+        assert!(!(msg.flags & TelegramFlag::ExpectReply));
+    }
+
+    #[test]
+    fn test_format() {
+        let outcome = Outcome::Reply {
+            data: Buffer::from_slice(&[0x01, 0xAB]),
+            attempts: 1,
+        };
+        assert_eq!(format_outcome(&outcome), "0201ab");
+        assert_eq!(format_outcome(&Outcome::BroadcastSent), "done");
+
+        let id = Identification {
+            manufacturer: 0xB5,
+            device_id: *b"BAI00",
+            sw_version: Version { major: 1, minor: 4 },
+            hw_version: Version {
+                major: 74,
+                minor: 1,
+            },
+        };
+        assert_eq!(format_identification(0x08, &id), "08;b5;BAI00;0104;7401");
+    }
+
+    #[test]
+    fn test_format_transaction() {
+        let mut transaction = Transaction {
+            telegram: Telegram {
+                src: 0x31,
+                dest: 0x08,
+                service: 0x0704,
+                data: Buffer::from_slice(&[]),
+            },
+            telegram_crc_ok: true,
+            slave_ack: Some(0x00),
+            reply: Some(Buffer::from_slice(&[0x01, 0xAB])),
+            reply_crc_ok: Some(true),
+            master_ack: Some(0x00),
+        };
+        assert_eq!(format_transaction(&transaction), "3108070400 / 0201ab");
+
+        transaction.reply_crc_ok = Some(false);
+        assert_eq!(
+            format_transaction(&transaction),
+            "3108070400 / ERR: CRC error"
+        );
+
+        transaction.slave_ack = Some(0xFF);
+        transaction.reply = None;
+        assert_eq!(
+            format_transaction(&transaction),
+            "3108070400 / ERR: NAK received"
+        );
+
+        transaction.slave_ack = None;
+        assert_eq!(
+            format_transaction(&transaction),
+            "3108070400 / ERR: read timeout"
+        );
+
+        // nothing to answer
+        transaction.telegram.dest = 0xFE;
+        assert_eq!(format_transaction(&transaction), "31fe070400");
+        transaction.telegram.dest = 0x10;
+        transaction.slave_ack = Some(0x00);
+        assert_eq!(format_transaction(&transaction), "3110070400");
+    }
+}
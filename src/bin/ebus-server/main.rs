@@ -0,0 +1,419 @@
+//! Runs the driver on a serial device (or TCP bridge) and serves a subset of the ebusd
+//! command protocol on a TCP port (feature `server`).
+//!
+//! ```text
+//! ebus-server -d /dev/ttyUSB0 -a 31 -p 8888
+//! echo "hex 08070400" | nc localhost 8888
+//! ```
+
+mod command;
+
+use std::{
+    collections::BTreeMap,
+    io,
+    net::IpAddr,
+    process::ExitCode,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use ebus::{
+    host::{self, Handle, HostDriver},
+    scan::Scanner,
+    service::Identification,
+    Address, EbusDriver, ProcessResult,
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    sync::broadcast::{self, error::RecvError},
+    task::JoinHandle,
+    time,
+};
+use tokio_serial::SerialPortBuilderExt;
+
+use command::{Error, Request};
+
+const ARBITRATION_DELAY: Duration = Duration::from_micros(540);
+/// Give up on a telegram if the bus stays silent for this long
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+/// Signal is lost if nothing was received for this long
+const SIGNAL_TIMEOUT: Duration = Duration::from_secs(5);
+
+const USAGE: &str = "\
+usage: ebus-server [-d DEVICE] [-a ADDR] [-p PORT] [-b BIND] [--auto-syn]
+
+ -d DEVICE   /dev/ttyX        serial device or pseudo-terminal (2400 baud)
+             enh:/dev/ttyX    enhanced protocol adapter (9600 baud)
+             ens:/dev/ttyX    enhanced protocol adapter (115200 baud)
+             HOST:PORT        TCP bridge, `enh:HOST:PORT` for enhanced protocol
+             (default /dev/ttyUSB0)
+ -a ADDR     own master address in hex (default 31)
+ -p PORT     TCP port for clients (default 8888)
+ -b BIND     address to listen on (default 127.0.0.1)
+ --auto-syn  act as AUTO-SYN generator";
+
+enum Device {
+    Serial {
+        path: String,
+        baud: u32,
+        enhanced: bool,
+    },
+    Tcp {
+        addr: String,
+        enhanced: bool,
+    },
+}
+
+impl Device {
+    /// Device syntax of ebusd
+    fn parse(s: &str) -> Self {
+        let (rest, baud, enhanced) = if let Some(rest) = s.strip_prefix("ens:") {
+            (rest, 115_200, true)
+        } else if let Some(rest) = s.strip_prefix("enh:") {
+            (rest, 9600, true)
+        } else {
+            (s, 2400, false)
+        };
+
+        if rest.starts_with('/') {
+            Device::Serial {
+                path: rest.into(),
+                baud,
+                enhanced,
+            }
+        } else {
+            Device::Tcp {
+                addr: rest.into(),
+                enhanced,
+            }
+        }
+    }
+}
+
+struct Args {
+    device: String,
+    address: u8,
+    port: u16,
+    bind: IpAddr,
+    auto_syn: bool,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = Args {
+            device: "/dev/ttyUSB0".into(),
+            address: 0x31,
+            port: 8888,
+            bind: [127, 0, 0, 1].into(),
+            auto_syn: false,
+        };
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("missing value for {arg}"));
+            match arg.as_str() {
+                "-d" => parsed.device = value()?,
+                "-a" => {
+                    let value = value()?;
+                    parsed.address = u8::from_str_radix(&value, 16)
+                        .ok()
+                        .filter(|&addr| Address(addr).is_master())
+                        .ok_or(format!("invalid master address {value}"))?;
+                }
+                "-p" => {
+                    let value = value()?;
+                    parsed.port = value.parse().map_err(|_| format!("invalid port {value}"))?;
+                }
+                "-b" => {
+                    let value = value()?;
+                    parsed.bind = value
+                        .parse()
+                        .map_err(|_| format!("invalid address {value}"))?;
+                }
+                "--auto-syn" => parsed.auto_syn = true,
+                _ => return Err(format!("unknown argument {arg}")),
+            }
+        }
+
+        Ok(parsed)
+    }
+}
+
+/// State shared by all clients
+struct Shared {
+    handle: Handle,
+    src: u8,
+    device: String,
+    /// When the last byte was received
+    last_rx: Mutex<Option<Instant>>,
+    scan: Mutex<Scan>,
+}
+
+#[derive(Default)]
+struct Scan {
+    running: bool,
+    devices: BTreeMap<u8, Identification>,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) if e == "unknown argument --help" => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("{e}\n\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match run(args).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("ebus-server: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(args: Args) -> io::Result<()> {
+    let mut driver = EbusDriver::new(ARBITRATION_DELAY, 0x9B, 0x5C, 8);
+    driver
+        .set_master_address(args.address)
+        .expect("checked while parsing");
+    driver.set_auto_syn(args.auto_syn);
+
+    let (handle, bus) = match Device::parse(&args.device) {
+        Device::Serial {
+            path,
+            baud,
+            enhanced,
+        } => {
+            let io = tokio_serial::new(path, baud).open_native_async()?;
+            spawn_driver(driver, io, enhanced)
+        }
+        Device::Tcp { addr, enhanced } => {
+            let io = TcpStream::connect(addr).await?;
+            spawn_driver(driver, io, enhanced)
+        }
+    };
+
+    let shared = Arc::new(Shared {
+        handle,
+        src: args.address,
+        device: args.device,
+        last_rx: Mutex::new(None),
+        scan: Mutex::default(),
+    });
+    tokio::spawn(track_signal(shared.clone()));
+
+    let listener = TcpListener::bind((args.bind, args.port)).await?;
+    let accept = async {
+        loop {
+            let (stream, _) = listener.accept().await?;
+            tokio::spawn(serve(stream, shared.clone()));
+        }
+    };
+
+    tokio::select! {
+        res = bus => {
+            res.map_err(io::Error::other)??;
+            Err(io::Error::new(io::ErrorKind::UnexpectedEof, "device closed"))
+        }
+        res = accept => res,
+    }
+}
+
+fn spawn_driver<S>(
+    driver: EbusDriver,
+    io: S,
+    enhanced: bool,
+) -> (Handle, JoinHandle<io::Result<()>>)
+where
+    S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    let mut host = if enhanced {
+        HostDriver::new_enhanced(driver, io)
+    } else {
+        HostDriver::new(driver, io)
+    };
+    // for `listen`
+    host.set_sniffer(Some(EbusDriver::new(Duration::ZERO, 0x9B, 0x5C, 0)));
+
+    (host.handle(), tokio::spawn(host.run()))
+}
+
+async fn track_signal(shared: Arc<Shared>) {
+    let mut results = shared.handle.subscribe();
+    loop {
+        match results.recv().await {
+            Ok(_) | Err(RecvError::Lagged(_)) => {
+                *shared.last_rx.lock().unwrap() = Some(Instant::now());
+            }
+            Err(RecvError::Closed) => return,
+        }
+    }
+}
+
+/// Talk to one client until it disconnects
+async fn serve(stream: TcpStream, shared: Arc<Shared>) -> io::Result<()> {
+    let (rx, mut tx) = stream.into_split();
+    let mut lines = BufReader::new(rx).lines();
+    let mut listen: Option<broadcast::Receiver<Arc<ProcessResult>>> = None;
+
+    loop {
+        let line = match &mut listen {
+            None => lines.next_line().await?,
+            Some(results) => tokio::select! {
+                line = lines.next_line() => line?,
+                res = results.recv() => {
+                    match res {
+                        Ok(res) => {
+                            if let Some(line) = listened(&res) {
+                                tx.write_all(format!("{line}\n").as_bytes()).await?;
+                            }
+                        }
+                        Err(RecvError::Lagged(_)) => {}
+                        Err(RecvError::Closed) => listen = None,
+                    }
+                    continue;
+                }
+            },
+        };
+        let Some(line) = line else {
+            return Ok(());
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let answer = match Request::parse(&line) {
+            Ok(Request::Quit) => return Ok(()),
+            Ok(Request::Listen) => {
+                listen = Some(shared.handle.subscribe());
+                "listen started".into()
+            }
+            Ok(Request::ListenStop) => {
+                listen = None;
+                "listen stopped".into()
+            }
+            Ok(request) => execute(request, &shared).await,
+            Err(e) => e.to_string(),
+        };
+        tx.write_all(format!("{answer}\n\n").as_bytes()).await?;
+    }
+}
+
+async fn execute(request: Request, shared: &Arc<Shared>) -> String {
+    match request {
+        Request::Hex { src, telegram } => {
+            let msg = command::master_telegram(src.unwrap_or(shared.src), telegram);
+            match send(&shared.handle, msg).await {
+                Ok(outcome) => command::format_outcome(&outcome),
+                Err(e) => e.to_string(),
+            }
+        }
+        Request::ScanOne { dest } => match identify(shared, dest).await {
+            Ok(id) => command::format_identification(dest, &id),
+            Err(e) => e.to_string(),
+        },
+        Request::ScanFull => {
+            let mut scan = shared.scan.lock().unwrap();
+            if scan.running {
+                return "ERR: scan already running".into();
+            }
+            scan.running = true;
+            tokio::spawn(scan_all(shared.clone()));
+            "scan initiated".into()
+        }
+        Request::ScanResult => {
+            let scan = shared.scan.lock().unwrap();
+            if scan.devices.is_empty() {
+                return "ERR: no devices found".into();
+            }
+            let lines: Vec<_> = scan
+                .devices
+                .iter()
+                .map(|(&addr, id)| command::format_identification(addr, id))
+                .collect();
+            lines.join("\n")
+        }
+        Request::Info => {
+            let signal = match *shared.last_rx.lock().unwrap() {
+                Some(at) if at.elapsed() < SIGNAL_TIMEOUT => "acquired",
+                _ => "no signal",
+            };
+            let scan = if shared.scan.lock().unwrap().running {
+                "running"
+            } else {
+                "idle"
+            };
+            format!(
+                "version: ebus-server {}\ndevice: {}\nsignal: {signal}\naddress: {:02x}\nscan: {scan}",
+                env!("CARGO_PKG_VERSION"),
+                shared.device,
+                shared.src,
+            )
+        }
+        Request::Help => command::HELP.into(),
+        Request::Listen | Request::ListenStop | Request::Quit => unreachable!(),
+    }
+}
+
+async fn send(handle: &Handle, msg: ebus::MasterTelegram) -> Result<ebus::Outcome, Error> {
+    // dropping the future takes the telegram out of the queue
+    match time::timeout(SEND_TIMEOUT, handle.send(msg)).await {
+        Ok(Ok(outcome)) => Ok(outcome),
+        Ok(Err(host::Error::Send(e))) => Err(e.into()),
+        Ok(Err(host::Error::Closed)) | Err(_) => Err(Error::NoSignal),
+    }
+}
+
+async fn identify(shared: &Shared, dest: u8) -> Result<Identification, Error> {
+    let outcome = send(&shared.handle, Identification::request(shared.src, dest)).await?;
+    let ebus::Outcome::Reply { data, .. } = outcome else {
+        return Err(Error::Timeout);
+    };
+    let id = Identification::parse(data.as_bytes()).map_err(|_| Error::InvalidLength)?;
+    shared.scan.lock().unwrap().devices.insert(dest, id.clone());
+
+    Ok(id)
+}
+
+/// Send an identification request to every slave address but our own
+async fn scan_all(shared: Arc<Shared>) {
+    let mut scanner = Scanner::new(shared.src);
+    while let Some(msg) = scanner.next_msg().cloned() {
+        let outcome = match time::timeout(SEND_TIMEOUT, shared.handle.send(msg)).await {
+            Ok(Ok(outcome)) => Ok(outcome),
+            Ok(Err(host::Error::Send(e))) => Err(e),
+            // bus is gone, no point in going on
+            Ok(Err(host::Error::Closed)) | Err(_) => break,
+        };
+        let target = scanner.target();
+        scanner.handle_outcome(outcome);
+
+        if let Some((addr, id)) = scanner.devices().find(|(addr, _)| Some(addr.0) == target) {
+            shared
+                .scan
+                .lock()
+                .unwrap()
+                .devices
+                .insert(addr.0, id.clone());
+        }
+    }
+
+    shared.scan.lock().unwrap().running = false;
+}
+
+/// Line printed by `listen` for a result, every transaction comes from the sniffer
+fn listened(res: &ProcessResult) -> Option<String> {
+    match res {
+        ProcessResult::Transaction { transaction } => {
+            Some(command::format_transaction(transaction))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,75 @@
+use core::time::Duration;
+
+/// Source of time for the driver
+pub trait Clock {
+    /// Monotonic time since an arbitrary epoch
+    fn now(&self) -> Duration;
+
+    /// Block for `duration`, only used for the (sub-ms) arbitration delay
+    fn delay(&self, duration: Duration);
+}
+
+impl<C: Clock + ?Sized> Clock for &C {
+    fn now(&self) -> Duration {
+        (**self).now()
+    }
+
+    fn delay(&self, duration: Duration) {
+        (**self).delay(duration)
+    }
+}
+
+/// [`Clock`] based on `std::time::Instant` and `std::thread::sleep`
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug)]
+pub struct StdClock {
+    epoch: std::time::Instant,
+}
+
+#[cfg(feature = "std")]
+impl Default for StdClock {
+    fn default() -> Self {
+        StdClock {
+            epoch: std::time::Instant::now(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Clock for StdClock {
+    fn now(&self) -> Duration {
+        self.epoch.elapsed()
+    }
+
+    fn delay(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+/// Records the arbitration delay instead of blocking, so async drivers can await it
+#[cfg(any(feature = "async", feature = "tokio"))]
+pub(crate) struct DeferredDelay<C> {
+    pub clock: C,
+    pub delay: core::cell::Cell<Option<Duration>>,
+}
+
+#[cfg(any(feature = "async", feature = "tokio"))]
+impl<C: Clock> DeferredDelay<C> {
+    pub fn new(clock: C) -> Self {
+        DeferredDelay {
+            clock,
+            delay: Default::default(),
+        }
+    }
+}
+
+#[cfg(any(feature = "async", feature = "tokio"))]
+impl<C: Clock> Clock for DeferredDelay<C> {
+    fn now(&self) -> Duration {
+        self.clock.now()
+    }
+
+    fn delay(&self, duration: Duration) {
+        self.delay.set(Some(duration));
+    }
+}
@@ -0,0 +1,397 @@
+//! Data types of the eBUS application layer.
+//!
+//! Every type has a replacement value which signals "value not available".
+//! It is decoded as `None` and written when encoding `None`.
+
+use crate::Buffer;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CodecError {
+    /// Not enough bytes at the given offset
+    OutOfBounds,
+    /// Value cannot be represented by the data type
+    OutOfRange,
+    /// Bytes do not form a valid value (e.g. BCD nibble > 9)
+    Invalid,
+}
+
+pub trait DataType {
+    type Value;
+
+    /// Number of bytes on the bus
+    const LEN: usize;
+
+    /// Decode from `bytes[..LEN]`, `None` is the replacement value
+    fn decode(bytes: &[u8]) -> Result<Option<Self::Value>, CodecError>;
+
+    /// Encode into `bytes[..LEN]`, `None` writes the replacement value
+    fn encode(value: Option<Self::Value>, bytes: &mut [u8]) -> Result<(), CodecError>;
+}
+
+/// BYTE / CHAR: unsigned, 0 to 254
+pub struct Char;
+
+/// SIGNED CHAR / DATA1b: signed, -127 to 127
+pub struct Data1b;
+
+/// DATA1c: unsigned with resolution 0.5, 0 to 100
+pub struct Data1c;
+
+/// BCD: one byte with two decimal digits, 0 to 99
+pub struct Bcd;
+
+/// WORD: unsigned, LSB first, 0 to 65534
+pub struct Word;
+
+/// SIGNED INTEGER: signed, LSB first, -32767 to 32767
+pub struct Int;
+
+/// DATA2b: signed with resolution 1/256, -127.99 to 127.99
+pub struct Data2b;
+
+/// DATA2c: signed with resolution 1/16, -2047.9 to 2047.9
+pub struct Data2c;
+
+fn slice<const N: usize>(bytes: &[u8]) -> Result<[u8; N], CodecError> {
+    bytes
+        .get(..N)
+        .and_then(|b| b.try_into().ok())
+        .ok_or(CodecError::OutOfBounds)
+}
+
+fn slice_mut<const N: usize>(bytes: &mut [u8]) -> Result<&mut [u8; N], CodecError> {
+    bytes
+        .get_mut(..N)
+        .and_then(|b| b.try_into().ok())
+        .ok_or(CodecError::OutOfBounds)
+}
+
+/// Scale and round to nearest (`f32::round` is not available in `core`)
+fn scale(value: f32, factor: f32) -> f32 {
+    let scaled = value * factor;
+    if scaled < 0.0 {
+        scaled - 0.5
+    } else {
+        scaled + 0.5
+    }
+}
+
+/// Scale and round to a 16 bit integer, `i16::MIN` is the replacement value
+fn scale_int(value: f32, factor: f32) -> Result<i16, CodecError> {
+    // saturating, NaN becomes 0
+    let scaled = scale(value, factor) as i32;
+    if value.is_nan() || !(-32767..=32767).contains(&scaled) {
+        return Err(CodecError::OutOfRange);
+    }
+
+    Ok(scaled as i16)
+}
+
+impl DataType for Char {
+    type Value = u8;
+    const LEN: usize = 1;
+
+    fn decode(bytes: &[u8]) -> Result<Option<u8>, CodecError> {
+        let [b] = slice(bytes)?;
+        Ok((b != 0xFF).then_some(b))
+    }
+
+    fn encode(value: Option<u8>, bytes: &mut [u8]) -> Result<(), CodecError> {
+        let b = match value {
+            Some(0xFF) => return Err(CodecError::OutOfRange),
+            Some(v) => v,
+            None => 0xFF,
+        };
+        *slice_mut(bytes)? = [b];
+
+        Ok(())
+    }
+}
+
+impl DataType for Data1b {
+    type Value = i8;
+    const LEN: usize = 1;
+
+    fn decode(bytes: &[u8]) -> Result<Option<i8>, CodecError> {
+        let [b] = slice(bytes)?;
+        Ok((b != 0x80).then_some(b as i8))
+    }
+
+    fn encode(value: Option<i8>, bytes: &mut [u8]) -> Result<(), CodecError> {
+        let b = match value {
+            Some(i8::MIN) => return Err(CodecError::OutOfRange),
+            Some(v) => v as u8,
+            None => 0x80,
+        };
+        *slice_mut(bytes)? = [b];
+
+        Ok(())
+    }
+}
+
+impl DataType for Data1c {
+    type Value = f32;
+    const LEN: usize = 1;
+
+    fn decode(bytes: &[u8]) -> Result<Option<f32>, CodecError> {
+        match slice(bytes)? {
+            [0xFF] => Ok(None),
+            [b] if b <= 200 => Ok(Some(b as f32 / 2.0)),
+            _ => Err(CodecError::Invalid),
+        }
+    }
+
+    fn encode(value: Option<f32>, bytes: &mut [u8]) -> Result<(), CodecError> {
+        let b = match value {
+            Some(v) if (0.0..=100.0).contains(&v) => scale(v, 2.0) as u8,
+            Some(_) => return Err(CodecError::OutOfRange),
+            None => 0xFF,
+        };
+        *slice_mut(bytes)? = [b];
+
+        Ok(())
+    }
+}
+
+impl DataType for Bcd {
+    type Value = u8;
+    const LEN: usize = 1;
+
+    fn decode(bytes: &[u8]) -> Result<Option<u8>, CodecError> {
+        match slice(bytes)? {
+            [0xFF] => Ok(None),
+            [b] if b >> 4 <= 9 && b & 0x0F <= 9 => Ok(Some((b >> 4) * 10 + (b & 0x0F))),
+            _ => Err(CodecError::Invalid),
+        }
+    }
+
+    fn encode(value: Option<u8>, bytes: &mut [u8]) -> Result<(), CodecError> {
+        let b = match value {
+            Some(v) if v <= 99 => ((v / 10) << 4) | (v % 10),
+            Some(_) => return Err(CodecError::OutOfRange),
+            None => 0xFF,
+        };
+        *slice_mut(bytes)? = [b];
+
+        Ok(())
+    }
+}
+
+impl DataType for Word {
+    type Value = u16;
+    const LEN: usize = 2;
+
+    fn decode(bytes: &[u8]) -> Result<Option<u16>, CodecError> {
+        let w = u16::from_le_bytes(slice(bytes)?);
+        Ok((w != 0xFFFF).then_some(w))
+    }
+
+    fn encode(value: Option<u16>, bytes: &mut [u8]) -> Result<(), CodecError> {
+        let w = match value {
+            Some(0xFFFF) => return Err(CodecError::OutOfRange),
+            Some(v) => v,
+            None => 0xFFFF,
+        };
+        *slice_mut(bytes)? = w.to_le_bytes();
+
+        Ok(())
+    }
+}
+
+impl DataType for Int {
+    type Value = i16;
+    const LEN: usize = 2;
+
+    fn decode(bytes: &[u8]) -> Result<Option<i16>, CodecError> {
+        let i = i16::from_le_bytes(slice(bytes)?);
+        Ok((i != i16::MIN).then_some(i))
+    }
+
+    fn encode(value: Option<i16>, bytes: &mut [u8]) -> Result<(), CodecError> {
+        let i = match value {
+            Some(i16::MIN) => return Err(CodecError::OutOfRange),
+            Some(v) => v,
+            None => i16::MIN,
+        };
+        *slice_mut(bytes)? = i.to_le_bytes();
+
+        Ok(())
+    }
+}
+
+impl DataType for Data2b {
+    type Value = f32;
+    const LEN: usize = 2;
+
+    fn decode(bytes: &[u8]) -> Result<Option<f32>, CodecError> {
+        Ok(Int::decode(bytes)?.map(|i| i as f32 / 256.0))
+    }
+
+    fn encode(value: Option<f32>, bytes: &mut [u8]) -> Result<(), CodecError> {
+        let i = value.map(|v| scale_int(v, 256.0)).transpose()?;
+        Int::encode(i, bytes)
+    }
+}
+
+impl DataType for Data2c {
+    type Value = f32;
+    const LEN: usize = 2;
+
+    fn decode(bytes: &[u8]) -> Result<Option<f32>, CodecError> {
+        Ok(Int::decode(bytes)?.map(|i| i as f32 / 16.0))
+    }
+
+    fn encode(value: Option<f32>, bytes: &mut [u8]) -> Result<(), CodecError> {
+        let i = value.map(|v| scale_int(v, 16.0)).transpose()?;
+        Int::encode(i, bytes)
+    }
+}
+
+impl Buffer {
+    /// Decode a value of type `D` at `offset`
+    pub fn get<D: DataType>(&self, offset: usize) -> Result<Option<D::Value>, CodecError> {
+        let bytes = self
+            .as_bytes()
+            .get(offset..)
+            .ok_or(CodecError::OutOfBounds)?;
+        D::decode(bytes)
+    }
+
+    /// Encode a value of type `D` at `offset`, the buffer is not extended
+    pub fn set<D: DataType>(
+        &mut self,
+        offset: usize,
+        value: Option<D::Value>,
+    ) -> Result<(), CodecError> {
+        let bytes = self
+            .as_bytes_mut()
+            .get_mut(offset..)
+            .ok_or(CodecError::OutOfBounds)?;
+        D::encode(value, bytes)
+    }
+
+    /// BIT: read bit `bit` (0 = LSB) of the byte at `offset`
+    pub fn get_bit(&self, offset: usize, bit: u8) -> Result<bool, CodecError> {
+        let byte = self.as_bytes().get(offset).ok_or(CodecError::OutOfBounds)?;
+        Ok(byte & 1u8.checked_shl(bit as u32).ok_or(CodecError::OutOfRange)? != 0)
+    }
+
+    /// BIT: write bit `bit` (0 = LSB) of the byte at `offset`
+    pub fn set_bit(&mut self, offset: usize, bit: u8, value: bool) -> Result<(), CodecError> {
+        let mask = 1u8.checked_shl(bit as u32).ok_or(CodecError::OutOfRange)?;
+        let byte = self
+            .as_bytes_mut()
+            .get_mut(offset)
+            .ok_or(CodecError::OutOfBounds)?;
+        if value {
+            *byte |= mask;
+        } else {
+            *byte &= !mask;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        codec::{Bcd, Char, CodecError, Data1b, Data1c, Data2b, Data2c, Int, Word},
+        Buffer,
+    };
+
+    #[test]
+    fn test_data2c() {
+        // examples from the spec
+        let buf = Buffer::from_slice(&[0x00, 0x00, 0xFF, 0xFF, 0xF0, 0xFF, 0x01, 0x80]);
+        assert_eq!(buf.get::<Data2c>(0), Ok(Some(0.0)));
+        assert_eq!(buf.get::<Data2c>(2), Ok(Some(-0.0625)));
+        assert_eq!(buf.get::<Data2c>(4), Ok(Some(-1.0)));
+        assert_eq!(buf.get::<Data2c>(6), Ok(Some(-2047.9375)));
+        assert_eq!(buf.get::<Data2c>(7), Err(CodecError::OutOfBounds));
+
+        let mut buf = Buffer::from_slice(&[0; 4]);
+        buf.set::<Data2c>(0, Some(21.3)).unwrap();
+        buf.set::<Data2c>(2, None).unwrap();
+        assert_eq!(buf.as_bytes(), &[0x55, 0x01, 0x00, 0x80]);
+        assert_eq!(buf.get::<Data2c>(2), Ok(None));
+        assert_eq!(
+            buf.set::<Data2c>(0, Some(2048.0)),
+            Err(CodecError::OutOfRange)
+        );
+
+        // limits after rounding, -2048.0 would be the replacement value
+        buf.set::<Data2c>(0, Some(-2047.95)).unwrap();
+        assert_eq!(buf.get::<Data2c>(0), Ok(Some(-2047.9375)));
+        buf.set::<Data2c>(0, Some(2047.95)).unwrap();
+        assert_eq!(buf.get::<Data2c>(0), Ok(Some(2047.9375)));
+        for v in [-2047.99, 2047.99, f32::NAN] {
+            assert_eq!(buf.set::<Data2c>(0, Some(v)), Err(CodecError::OutOfRange));
+        }
+    }
+
+    #[test]
+    fn test_data2b() {
+        let buf = Buffer::from_slice(&[0x00, 0x01, 0x80, 0xFF, 0x01, 0x80]);
+        assert_eq!(buf.get::<Data2b>(0), Ok(Some(1.0)));
+        assert_eq!(buf.get::<Data2b>(2), Ok(Some(-0.5)));
+        assert_eq!(buf.get::<Data2b>(4), Ok(Some(-127.99609)));
+
+        let mut buf = Buffer::from_slice(&[0; 2]);
+        buf.set::<Data2b>(0, Some(-1.0)).unwrap();
+        assert_eq!(buf.as_bytes(), &[0x00, 0xFF]);
+
+        // limits after rounding, -128.0 would be the replacement value
+        buf.set::<Data2b>(0, Some(-127.997)).unwrap();
+        assert_eq!(buf.get::<Data2b>(0), Ok(Some(-127.99609)));
+        buf.set::<Data2b>(0, Some(127.997)).unwrap();
+        assert_eq!(buf.get::<Data2b>(0), Ok(Some(127.99609)));
+        for v in [-127.999, 127.999, 128.0] {
+            assert_eq!(buf.set::<Data2b>(0, Some(v)), Err(CodecError::OutOfRange));
+        }
+    }
+
+    #[test]
+    fn test_single_byte() {
+        let buf = Buffer::from_slice(&[0xFF, 0x80, 0x81, 0x99, 0x3A, 0xC8]);
+        assert_eq!(buf.get::<Char>(0), Ok(None));
+        assert_eq!(buf.get::<Data1b>(1), Ok(None));
+        assert_eq!(buf.get::<Data1b>(2), Ok(Some(-127)));
+        assert_eq!(buf.get::<Bcd>(3), Ok(Some(99)));
+        assert_eq!(buf.get::<Bcd>(4), Err(CodecError::Invalid));
+        assert_eq!(buf.get::<Data1c>(5), Ok(Some(100.0)));
+
+        let mut buf = Buffer::from_slice(&[0; 3]);
+        buf.set::<Bcd>(0, Some(42)).unwrap();
+        buf.set::<Data1c>(1, Some(20.5)).unwrap();
+        buf.set::<Data1b>(2, Some(-1)).unwrap();
+        assert_eq!(buf.as_bytes(), &[0x42, 41, 0xFF]);
+        assert_eq!(buf.set::<Bcd>(0, Some(100)), Err(CodecError::OutOfRange));
+        assert_eq!(buf.set::<Char>(0, Some(0xFF)), Err(CodecError::OutOfRange));
+    }
+
+    #[test]
+    fn test_word() {
+        let mut buf = Buffer::from_slice(&[0x34, 0x12, 0xFF, 0xFF]);
+        assert_eq!(buf.get::<Word>(0), Ok(Some(0x1234)));
+        assert_eq!(buf.get::<Word>(2), Ok(None));
+        assert_eq!(buf.get::<Int>(2), Ok(Some(-1)));
+
+        buf.set::<Int>(0, None).unwrap();
+        assert_eq!(buf.as_bytes(), &[0x00, 0x80, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn test_bit() {
+        let mut buf = Buffer::from_slice(&[0b0000_0100]);
+        assert_eq!(buf.get_bit(0, 2), Ok(true));
+        assert_eq!(buf.get_bit(0, 3), Ok(false));
+        assert_eq!(buf.get_bit(0, 8), Err(CodecError::OutOfRange));
+
+        buf.set_bit(0, 7, true).unwrap();
+        buf.set_bit(0, 2, false).unwrap();
+        assert_eq!(buf.as_bytes(), &[0x80]);
+    }
+}
@@ -1,4 +1,5 @@
 #[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Crc {
     crc: u8,
     polynom: u8,
@@ -0,0 +1,342 @@
+//! Date and time types, e.g. as sent in the 0x0700 date/time broadcast.
+//!
+//! They implement [`DataType`] and can be read directly from a `Buffer`:
+//!
+//! ```
+//! use ebus::{datetime::{Bda, Bti}, Buffer};
+//!
+//! // DATA2b outdoor temperature, then time (ss mm hh) and date (dd mm ww yy)
+//! let data = Buffer::from_slice(&[0x00, 0x08, 0x35, 0x14, 0x18, 0x10, 0x03, 0x04, 0x23]);
+//! let time = data.get::<Bti>(2).unwrap().unwrap();
+//! let date = data.get::<Bda>(5).unwrap().unwrap();
+//! assert_eq!((time.hours, time.minutes, time.seconds), (18, 14, 35));
+//! assert_eq!((date.day, date.month, date.year), (10, 3, 2023));
+//! ```
+
+use crate::codec::{CodecError, DataType};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Time {
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Date {
+    pub day: u8,
+    pub month: u8,
+    /// Full year, the bus only carries 2000 to 2099
+    pub year: u16,
+    /// Only present for formats carrying a weekday
+    pub weekday: Option<Weekday>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u8)]
+pub enum Weekday {
+    Monday = 0,
+    Tuesday = 1,
+    Wednesday = 2,
+    Thursday = 3,
+    Friday = 4,
+    Saturday = 5,
+    Sunday = 6,
+}
+
+/// BTI: time in BCD, reverse order (ss mm hh)
+pub struct Bti;
+
+/// HTI: time in hex (hh mm ss)
+pub struct Hti;
+
+/// BDA: date in BCD with weekday (dd mm ww yy)
+pub struct Bda;
+
+/// HDA: date in hex (dd mm yy)
+pub struct Hda;
+
+/// DAY: weekday, 0x00 (Monday) to 0x06 (Sunday)
+pub struct Day;
+
+const REPLACEMENT: u8 = 0xFF;
+
+impl Time {
+    pub fn new(hours: u8, minutes: u8, seconds: u8) -> Result<Self, CodecError> {
+        if hours > 23 || minutes > 59 || seconds > 59 {
+            return Err(CodecError::Invalid);
+        }
+
+        Ok(Time {
+            hours,
+            minutes,
+            seconds,
+        })
+    }
+}
+
+impl Date {
+    pub fn new(
+        day: u8,
+        month: u8,
+        year: u16,
+        weekday: Option<Weekday>,
+    ) -> Result<Self, CodecError> {
+        if !(2000..=2099).contains(&year) || !(1..=12).contains(&month) {
+            return Err(CodecError::Invalid);
+        }
+        if day == 0 || day > days_in_month(month, year) {
+            return Err(CodecError::Invalid);
+        }
+
+        Ok(Date {
+            day,
+            month,
+            year,
+            weekday,
+        })
+    }
+
+    fn year_short(&self) -> u8 {
+        (self.year - 2000) as u8
+    }
+}
+
+impl Weekday {
+    pub fn from_u8(day: u8) -> Option<Self> {
+        use Weekday::*;
+
+        Some(match day {
+            0 => Monday,
+            1 => Tuesday,
+            2 => Wednesday,
+            3 => Thursday,
+            4 => Friday,
+            5 => Saturday,
+            6 => Sunday,
+            _ => return None,
+        })
+    }
+}
+
+fn days_in_month(month: u8, year: u16) -> u8 {
+    match month {
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => {
+            29
+        }
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+fn from_bcd(byte: u8) -> Result<u8, CodecError> {
+    if byte >> 4 > 9 || byte & 0x0F > 9 {
+        return Err(CodecError::Invalid);
+    }
+
+    Ok((byte >> 4) * 10 + (byte & 0x0F))
+}
+
+fn to_bcd(value: u8) -> u8 {
+    ((value / 10) << 4) | (value % 10)
+}
+
+fn bytes<const N: usize>(bytes: &[u8]) -> Result<[u8; N], CodecError> {
+    bytes
+        .get(..N)
+        .and_then(|b| b.try_into().ok())
+        .ok_or(CodecError::OutOfBounds)
+}
+
+fn write<const N: usize>(bytes: &mut [u8], value: [u8; N]) -> Result<(), CodecError> {
+    bytes
+        .get_mut(..N)
+        .ok_or(CodecError::OutOfBounds)?
+        .copy_from_slice(&value);
+
+    Ok(())
+}
+
+impl DataType for Bti {
+    type Value = Time;
+    const LEN: usize = 3;
+
+    fn decode(data: &[u8]) -> Result<Option<Time>, CodecError> {
+        match bytes::<3>(data)? {
+            [REPLACEMENT, ..] => Ok(None),
+            [s, m, h] => Time::new(from_bcd(h)?, from_bcd(m)?, from_bcd(s)?).map(Some),
+        }
+    }
+
+    fn encode(value: Option<Time>, data: &mut [u8]) -> Result<(), CodecError> {
+        let encoded = match value {
+            Some(t) => {
+                let t = Time::new(t.hours, t.minutes, t.seconds)?;
+                [to_bcd(t.seconds), to_bcd(t.minutes), to_bcd(t.hours)]
+            }
+            None => [REPLACEMENT; 3],
+        };
+        write(data, encoded)
+    }
+}
+
+impl DataType for Hti {
+    type Value = Time;
+    const LEN: usize = 3;
+
+    fn decode(data: &[u8]) -> Result<Option<Time>, CodecError> {
+        match bytes::<3>(data)? {
+            [REPLACEMENT, ..] => Ok(None),
+            [h, m, s] => Time::new(h, m, s).map(Some),
+        }
+    }
+
+    fn encode(value: Option<Time>, data: &mut [u8]) -> Result<(), CodecError> {
+        let encoded = match value {
+            Some(t) => {
+                let t = Time::new(t.hours, t.minutes, t.seconds)?;
+                [t.hours, t.minutes, t.seconds]
+            }
+            None => [REPLACEMENT; 3],
+        };
+        write(data, encoded)
+    }
+}
+
+impl DataType for Bda {
+    type Value = Date;
+    const LEN: usize = 4;
+
+    fn decode(data: &[u8]) -> Result<Option<Date>, CodecError> {
+        match bytes::<4>(data)? {
+            [REPLACEMENT, ..] => Ok(None),
+            [d, m, w, y] => {
+                let weekday = Weekday::from_u8(from_bcd(w)?).ok_or(CodecError::Invalid)?;
+                Date::new(
+                    from_bcd(d)?,
+                    from_bcd(m)?,
+                    2000 + from_bcd(y)? as u16,
+                    Some(weekday),
+                )
+                .map(Some)
+            }
+        }
+    }
+
+    fn encode(value: Option<Date>, data: &mut [u8]) -> Result<(), CodecError> {
+        let encoded = match value {
+            Some(d) => {
+                let d = Date::new(d.day, d.month, d.year, d.weekday)?;
+                // the weekday is mandatory for this format
+                let w = d.weekday.ok_or(CodecError::Invalid)?;
+                [
+                    to_bcd(d.day),
+                    to_bcd(d.month),
+                    w as u8,
+                    to_bcd(d.year_short()),
+                ]
+            }
+            None => [REPLACEMENT; 4],
+        };
+        write(data, encoded)
+    }
+}
+
+impl DataType for Hda {
+    type Value = Date;
+    const LEN: usize = 3;
+
+    fn decode(data: &[u8]) -> Result<Option<Date>, CodecError> {
+        match bytes::<3>(data)? {
+            [REPLACEMENT, ..] => Ok(None),
+            [d, m, y] if y <= 99 => Date::new(d, m, 2000 + y as u16, None).map(Some),
+            _ => Err(CodecError::Invalid),
+        }
+    }
+
+    fn encode(value: Option<Date>, data: &mut [u8]) -> Result<(), CodecError> {
+        let encoded = match value {
+            Some(d) => {
+                let d = Date::new(d.day, d.month, d.year, None)?;
+                [d.day, d.month, d.year_short()]
+            }
+            None => [REPLACEMENT; 3],
+        };
+        write(data, encoded)
+    }
+}
+
+impl DataType for Day {
+    type Value = Weekday;
+    const LEN: usize = 1;
+
+    fn decode(data: &[u8]) -> Result<Option<Weekday>, CodecError> {
+        match bytes::<1>(data)? {
+            [REPLACEMENT] => Ok(None),
+            [w] => Weekday::from_u8(w).map(Some).ok_or(CodecError::Invalid),
+        }
+    }
+
+    fn encode(value: Option<Weekday>, data: &mut [u8]) -> Result<(), CodecError> {
+        write(data, [value.map_or(REPLACEMENT, |w| w as u8)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        codec::CodecError,
+        datetime::{Bda, Bti, Date, Day, Hda, Hti, Time, Weekday},
+        Buffer,
+    };
+
+    #[test]
+    fn test_round_trip_time() {
+        let time = Time::new(23, 59, 7).unwrap();
+        let mut buf = Buffer::from_slice(&[0; 6]);
+        buf.set::<Bti>(0, Some(time)).unwrap();
+        buf.set::<Hti>(3, Some(time)).unwrap();
+        assert_eq!(buf.as_bytes(), &[0x07, 0x59, 0x23, 23, 59, 7]);
+        assert_eq!(buf.get::<Bti>(0), Ok(Some(time)));
+        assert_eq!(buf.get::<Hti>(3), Ok(Some(time)));
+    }
+
+    #[test]
+    fn test_round_trip_date() {
+        let date = Date::new(29, 2, 2024, Some(Weekday::Thursday)).unwrap();
+        let mut buf = Buffer::from_slice(&[0; 8]);
+        buf.set::<Bda>(0, Some(date)).unwrap();
+        buf.set::<Hda>(4, Some(date)).unwrap();
+        buf.set::<Day>(7, date.weekday).unwrap();
+        assert_eq!(buf.as_bytes(), &[0x29, 0x02, 0x03, 0x24, 29, 2, 24, 3]);
+        assert_eq!(buf.get::<Bda>(0), Ok(Some(date)));
+        assert_eq!(
+            buf.get::<Hda>(4),
+            Ok(Some(Date {
+                weekday: None,
+                ..date
+            }))
+        );
+        assert_eq!(buf.get::<Day>(7), Ok(Some(Weekday::Thursday)));
+    }
+
+    #[test]
+    fn test_validation() {
+        assert_eq!(Time::new(24, 0, 0), Err(CodecError::Invalid));
+        assert_eq!(Date::new(29, 2, 2023, None), Err(CodecError::Invalid));
+        assert_eq!(Date::new(31, 4, 2023, None), Err(CodecError::Invalid));
+
+        let buf = Buffer::from_slice(&[0x60, 0x00, 0x12, 0xFF, 0xFF, 0xFF, 0x07]);
+        assert_eq!(buf.get::<Bti>(0), Err(CodecError::Invalid));
+        assert_eq!(buf.get::<Hti>(3), Ok(None));
+        assert_eq!(buf.get::<Day>(6), Err(CodecError::Invalid));
+        assert_eq!(buf.get::<Bda>(4), Err(CodecError::OutOfBounds));
+    }
+}
@@ -0,0 +1,210 @@
+//! ebusd "enhanced" adapter protocol.
+//!
+//! Adapters speaking it (e.g. eBUS adapter v3/v5) do the arbitration themselves. Every
+//! symbol is sent as two bytes `11cc ccdd 10dd dddd` (command `c`, data `d`), data bytes
+//! below `0x80` may also be sent as a single byte.
+//!
+//! Wrap the UART in [`AdapterTransmit`], enable
+//! [`EbusDriver::set_adapter_arbitration`](crate::EbusDriver::set_adapter_arbitration) and
+//! pass every [`Response`] from the [`Decoder`] to
+//! [`EbusDriver::process_response`](crate::EbusDriver::process_response).
+
+use crate::Transmit;
+
+const INIT: u8 = 0x0;
+const SEND: u8 = 0x1;
+const START: u8 = 0x2;
+const INFO: u8 = 0x3;
+const RESETTED: u8 = 0x0;
+const RECEIVED: u8 = 0x1;
+const STARTED: u8 = 0x2;
+const FAILED: u8 = 0xA;
+const ERROR_EBUS: u8 = 0xB;
+const ERROR_HOST: u8 = 0xC;
+
+/// Sent from host to adapter
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Command {
+    /// Reset the adapter, answered with [`Response::Resetted`]
+    Init { features: u8 },
+    /// Send a byte on the bus
+    Send(u8),
+    /// Arbitrate with the master address after the next SYN, answered with
+    /// [`Response::Started`] or [`Response::Failed`]
+    Start(u8),
+    /// Request adapter information
+    Info(u8),
+}
+
+impl Command {
+    /// Encode into `buf`, returns the bytes to send
+    pub fn encode(self, buf: &mut [u8; 2]) -> &[u8] {
+        let (cmd, data) = match self {
+            Command::Send(data) if data < 0x80 => {
+                buf[0] = data;
+                return &buf[..1];
+            }
+            Command::Init { features } => (INIT, features),
+            Command::Send(data) => (SEND, data),
+            Command::Start(src) => (START, src),
+            Command::Info(data) => (INFO, data),
+        };
+        *buf = [0xC0 | cmd << 2 | data >> 6, 0x80 | (data & 0x3F)];
+
+        buf
+    }
+}
+
+/// Sent from adapter to host
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Response {
+    /// Adapter has been reset
+    Resetted { features: u8 },
+    /// Byte received from the bus, including the ones we sent
+    Received(u8),
+    /// Arbitration won, our master address has been sent
+    Started(u8),
+    /// Adapter information
+    Info(u8),
+    /// Arbitration lost against the given master address
+    Failed(u8),
+    /// Error on the eBUS side (`0x00` framing, `0x01` overrun)
+    ErrorEbus(u8),
+    /// Error on the host side (`0x00` framing, `0x01` overrun)
+    ErrorHost(u8),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DecodeError {
+    /// Second byte of a symbol without a first one
+    MissingFirst(u8),
+    /// First byte of a symbol was not followed by a second one
+    MissingSecond(u8),
+    /// Unknown command with its data
+    UnknownCommand { command: u8, data: u8 },
+}
+
+/// Decodes the bytes received from an adapter into [`Response`]s
+#[derive(Debug, Default)]
+pub struct Decoder {
+    first: Option<u8>,
+}
+
+impl Decoder {
+    pub const fn new() -> Self {
+        Decoder { first: None }
+    }
+
+    /// Feed a received byte, returns a response once it is complete
+    pub fn feed(&mut self, byte: u8) -> Option<Result<Response, DecodeError>> {
+        match (self.first.take(), byte >> 6) {
+            (None, 0b00 | 0b01) => Some(Ok(Response::Received(byte))),
+            (None, 0b10) => Some(Err(DecodeError::MissingFirst(byte))),
+            (Some(first), 0b00 | 0b01) => Some(Err(DecodeError::MissingSecond(first))),
+            (Some(first), 0b10) => {
+                let command = (first >> 2) & 0x0F;
+                let data = first << 6 | (byte & 0x3F);
+
+                Some(match command {
+                    RESETTED => Ok(Response::Resetted { features: data }),
+                    RECEIVED => Ok(Response::Received(data)),
+                    STARTED => Ok(Response::Started(data)),
+                    INFO => Ok(Response::Info(data)),
+                    FAILED => Ok(Response::Failed(data)),
+                    ERROR_EBUS => Ok(Response::ErrorEbus(data)),
+                    ERROR_HOST => Ok(Response::ErrorHost(data)),
+                    command => Err(DecodeError::UnknownCommand { command, data }),
+                })
+            }
+            (first, _) => {
+                self.first = Some(byte);
+                first.map(|first| Err(DecodeError::MissingSecond(first)))
+            }
+        }
+    }
+}
+
+/// Sends bytes as [`Command::Send`] and arbitration requests as [`Command::Start`]
+#[derive(Debug, Default)]
+pub struct AdapterTransmit<T>(pub T);
+
+impl<T: Transmit> Transmit for AdapterTransmit<T> {
+    type Error = T::Error;
+
+    fn transmit_raw(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        let mut buf = [0; 2];
+        for &byte in bytes {
+            self.0.transmit_raw(Command::Send(byte).encode(&mut buf))?;
+        }
+
+        Ok(())
+    }
+
+    fn clear_buffer(&mut self) -> Result<(), Self::Error> {
+        self.0.clear_buffer()
+    }
+
+    fn transmit_start(&mut self, src: u8) -> Result<(), Self::Error> {
+        self.0.transmit_raw(Command::Start(src).encode(&mut [0; 2]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Command, DecodeError, Decoder, Response};
+
+    #[test]
+    fn test_encode() {
+        let mut buf = [0; 2];
+        assert_eq!(Command::Send(0x10).encode(&mut buf), [0x10]);
+        assert_eq!(Command::Send(0xAA).encode(&mut buf), [0xC6, 0xAA]);
+        assert_eq!(Command::Start(0xFF).encode(&mut buf), [0xCB, 0xBF]);
+        assert_eq!(
+            Command::Init { features: 0x01 }.encode(&mut buf),
+            [0xC0, 0x81]
+        );
+    }
+
+    #[test]
+    fn test_decode() {
+        let mut decoder = Decoder::new();
+        assert_eq!(decoder.feed(0x10), Some(Ok(Response::Received(0x10))));
+        assert_eq!(decoder.feed(0xC6), None);
+        assert_eq!(decoder.feed(0xAA), Some(Ok(Response::Received(0xAA))));
+        assert_eq!(decoder.feed(0xC8), None);
+        assert_eq!(decoder.feed(0x90), Some(Ok(Response::Started(0x10))));
+        assert_eq!(decoder.feed(0xEB), None);
+        assert_eq!(decoder.feed(0xBF), Some(Ok(Response::Failed(0xFF))));
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let mut decoder = Decoder::new();
+        assert_eq!(
+            decoder.feed(0x90),
+            Some(Err(DecodeError::MissingFirst(0x90)))
+        );
+        assert_eq!(decoder.feed(0xC6), None);
+        assert_eq!(
+            decoder.feed(0xC6),
+            Some(Err(DecodeError::MissingSecond(0xC6)))
+        );
+        assert_eq!(decoder.feed(0xAA), Some(Ok(Response::Received(0xAA))));
+        assert_eq!(decoder.feed(0xC6), None);
+        assert_eq!(
+            decoder.feed(0x10),
+            Some(Err(DecodeError::MissingSecond(0xC6)))
+        );
+        assert_eq!(decoder.feed(0xF4), None);
+        assert_eq!(
+            decoder.feed(0x81),
+            Some(Err(DecodeError::UnknownCommand {
+                command: 0xD,
+                data: 0x01
+            }))
+        );
+    }
+}
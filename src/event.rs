@@ -0,0 +1,101 @@
+use core::time::Duration;
+
+/// Protocol violations and other unusual conditions on the bus, see
+/// [`EbusDriver::drain_events`](crate::EbusDriver::drain_events).
+///
+/// The driver recovers from all of them on its own, they are reported for diagnostics.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BusEvent {
+    /// No byte was received for `gap` within a telegram, AUTO-SYN is missing
+    Gap { gap: Duration },
+    /// SYN while we were holding the bus lock, the participant we talked to did not answer
+    SynWhileLocked,
+    /// Second SYN while arbitrating, prevented us from locking the bus
+    DoubleSyn,
+    /// SYN of another AUTO-SYN generator, ours backs off
+    OtherAutoSyn,
+    /// Escape prefix was followed by `byte` instead of `0x00` or `0x01`
+    InvalidEscape { byte: u8 },
+    /// Arbitration was won by `winner`
+    LostArbitration { winner: u8 },
+    /// Refused to send a telegram with `src`, which is not a master address
+    InvalidSource { src: u8 },
+    /// Our telegram was answered with `byte` instead of an ACK
+    TelegramNotAcked { byte: u8 },
+    /// Our reply was answered with `byte` instead of an ACK
+    ReplyNotAcked { byte: u8 },
+    /// Telegram announced `len` data bytes, more than `MAX_BUF`
+    TelegramLenOverflow { len: u8 },
+    /// Reply announced `len` data bytes, more than `MAX_BUF`
+    ReplyLenOverflow { len: u8 },
+    /// Telegram CRC did not match
+    TelegramCrcError { expected: u8, received: u8 },
+    /// Reply CRC did not match
+    ReplyCrcError { expected: u8, received: u8 },
+    /// `byte` followed a reply we were vetting
+    VetInterrupted { byte: u8 },
+    /// We heard `byte` instead of our own ACK for a reply
+    OwnAckCorrupted { byte: u8 },
+    /// `byte` after a completed transaction while sniffing, SYN was expected
+    UnexpectedByte { byte: u8 },
+    /// Enhanced protocol adapter reported an error on the bus or, if `host`, on our side
+    AdapterError { code: u8, host: bool },
+}
+
+/// Number of events buffered by the driver, older ones are dropped
+pub const EVENT_QUEUE_LEN: usize = 8;
+
+/// Events not taken yet, oldest first
+#[derive(Debug, Default)]
+pub(crate) struct EventQueue {
+    events: [Option<BusEvent>; EVENT_QUEUE_LEN],
+    /// Index of the oldest event
+    head: usize,
+    len: usize,
+}
+
+impl EventQueue {
+    pub fn push(&mut self, event: BusEvent) {
+        if self.len == EVENT_QUEUE_LEN {
+            // drop the oldest
+            self.pop();
+        }
+        self.events[(self.head + self.len) % EVENT_QUEUE_LEN] = Some(event);
+        self.len += 1;
+    }
+
+    pub fn pop(&mut self) -> Option<BusEvent> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let event = self.events[self.head].take();
+        self.head = (self.head + 1) % EVENT_QUEUE_LEN;
+        self.len -= 1;
+        event
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BusEvent, EventQueue, EVENT_QUEUE_LEN};
+
+    #[test]
+    fn test_overflow() {
+        let mut queue = EventQueue::default();
+        for byte in 0..EVENT_QUEUE_LEN as u8 + 2 {
+            queue.push(BusEvent::UnexpectedByte { byte });
+        }
+
+        // the two oldest are gone
+        for expected in 2..EVENT_QUEUE_LEN as u8 + 2 {
+            assert_eq!(
+                queue.pop(),
+                Some(BusEvent::UnexpectedByte { byte: expected })
+            );
+        }
+        assert_eq!(queue.pop(), None);
+    }
+}
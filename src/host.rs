@@ -0,0 +1,321 @@
+//! Host driver on top of [`tokio`] (feature `tokio`).
+//!
+//! Works on any `AsyncRead + AsyncWrite`, e.g. a serial port of a USB adapter or a TCP
+//! stream to a bridge. Spawn [`HostDriver::run`] and talk to it through [`Handle`]s.
+//! Adapters speaking the ebusd enhanced protocol are supported with
+//! [`HostDriver::new_enhanced`].
+
+use core::{convert::Infallible, future::Future, time::Duration};
+use std::{io, sync::Arc, vec::Vec};
+
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    sync::{broadcast, mpsc, oneshot},
+    time,
+};
+
+use crate::{
+    clock::DeferredDelay,
+    enhanced::{Command, Decoder, Response},
+    EbusDriver, MasterTelegram, Outcome, ProcessResult, SendError, StdClock, Transmit,
+    AUTO_SYN_POLL,
+};
+
+/// Number of results a subscriber may fall behind before missing some
+const EVENT_CAPACITY: usize = 64;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The driver stopped running
+    Closed,
+    /// Telegram could not be sent
+    Send(SendError),
+}
+
+/// Queue telegrams and subscribe to results, cheap to clone
+#[derive(Clone)]
+pub struct Handle {
+    jobs: mpsc::UnboundedSender<Job>,
+    events: broadcast::Sender<Arc<ProcessResult>>,
+}
+
+impl Handle {
+    /// Queue a telegram, the future resolves once it has been sent.
+    ///
+    /// The telegram is queued right away, even if the future is never polled.
+    /// Telegrams are sent in the order they were queued.
+    pub fn send(&self, msg: MasterTelegram) -> impl Future<Output = Result<Outcome, Error>> {
+        let (done, outcome) = oneshot::channel();
+        let queued = self.jobs.send(Job { msg, done }).is_ok();
+
+        async move {
+            if !queued {
+                return Err(Error::Closed);
+            }
+
+            outcome
+                .await
+                .map_err(|_| Error::Closed)?
+                .map_err(Error::Send)
+        }
+    }
+
+    /// Receive every `ProcessResult` from now on, including the transactions of the
+    /// sniffer (see [`HostDriver::set_sniffer`])
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<ProcessResult>> {
+        self.events.subscribe()
+    }
+}
+
+struct Job {
+    msg: MasterTelegram,
+    done: oneshot::Sender<Result<Outcome, SendError>>,
+}
+
+/// Owns the stream and runs the receive loop of an [`EbusDriver`]
+pub struct HostDriver<S> {
+    driver: EbusDriver,
+    io: S,
+    handle: Handle,
+    jobs: mpsc::UnboundedReceiver<Job>,
+    /// Telegram currently being sent
+    current: Option<Job>,
+    clock: DeferredDelay<StdClock>,
+    tx: TxBuffer,
+    /// Only used with an enhanced protocol adapter
+    decoder: Option<Decoder>,
+    /// Follows the bus passively to report every transaction
+    sniffer: Option<EbusDriver>,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> HostDriver<S> {
+    pub fn new(driver: EbusDriver, io: S) -> Self {
+        let (jobs_tx, jobs) = mpsc::unbounded_channel();
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+
+        HostDriver {
+            driver,
+            io,
+            handle: Handle {
+                jobs: jobs_tx,
+                events,
+            },
+            jobs,
+            current: None,
+            clock: DeferredDelay::new(StdClock::default()),
+            tx: TxBuffer::default(),
+            decoder: None,
+            sniffer: None,
+        }
+    }
+
+    /// Talk to an adapter speaking the ebusd enhanced protocol, which does the arbitration
+    pub fn new_enhanced(mut driver: EbusDriver, io: S) -> Self {
+        driver.set_adapter_arbitration(true);
+        let mut this = Self::new(driver, io);
+        this.decoder = Some(Decoder::new());
+        this.tx.enhanced = true;
+
+        this
+    }
+
+    pub fn driver(&mut self) -> &mut EbusDriver {
+        &mut self.driver
+    }
+
+    /// Feed every byte on the bus into `sniffer` as well, which is put into sniffing mode.
+    ///
+    /// Subscribers then also receive `ProcessResult::Transaction` for every transaction,
+    /// ours included, with the reply of the slave and the ACKs.
+    pub fn set_sniffer(&mut self, sniffer: Option<EbusDriver>) {
+        self.sniffer = sniffer.map(|mut sniffer| {
+            sniffer.set_sniffing(true);
+            sniffer
+        });
+    }
+
+    pub fn handle(&self) -> Handle {
+        self.handle.clone()
+    }
+
+    /// Process bytes until the stream is closed or fails
+    pub async fn run(mut self) -> io::Result<()> {
+        let mut buf = [0; 64];
+        let mut vet = None;
+
+        loop {
+            let n = match vet.take() {
+                Some(timeout_ms) => {
+                    let read = self.io.read(&mut buf);
+                    match time::timeout(Duration::from_millis(timeout_ms as u64), read).await {
+                        Ok(n) => n?,
+                        Err(_) => {
+                            // no more bytes, acknowledge the reply; its echo is processed as usual
+                            let Ok(()) = self.driver.vet_timeout(&mut self.tx);
+                            self.flush().await?;
+                            continue;
+                        }
+                    }
+                }
+                None if self.driver.is_auto_syn_enabled() => {
+                    // wake up regularly to act as AUTO-SYN generator
+                    match time::timeout(AUTO_SYN_POLL, self.io.read(&mut buf)).await {
+                        Ok(n) => n?,
+                        Err(_) => {
+                            let Ok(()) = self.driver.poll(&mut self.tx, &self.clock);
+                            self.flush().await?;
+                            continue;
+                        }
+                    }
+                }
+                None => self.io.read(&mut buf).await?,
+            };
+            if n == 0 {
+                return Ok(());
+            }
+
+            for (i, &word) in buf[..n].iter().enumerate() {
+                let response = match &mut self.decoder {
+                    None => Response::Received(word),
+                    Some(decoder) => match decoder.feed(word) {
+                        None => continue,
+                        Some(Ok(response)) => response,
+                        Some(Err(_e)) => {
+                            warn!("invalid symbol from adapter: {:?}", _e);
+                            continue;
+                        }
+                    },
+                };
+                // a SYN is only fresh if nothing has been received after it
+                let is_low_latency = i == n - 1;
+                vet = self.step(response, is_low_latency).await?;
+            }
+        }
+    }
+
+    /// Process one byte, returns the vetting timeout if the reply has to be vetted
+    async fn step(&mut self, response: Response, is_low_latency: bool) -> io::Result<Option<u16>> {
+        while self.current.is_none() {
+            match self.jobs.try_recv() {
+                // nobody is waiting for the outcome anymore
+                Ok(job) if job.done.is_closed() => {}
+                Ok(job) => self.current = Some(job),
+                Err(_) => break,
+            }
+        }
+
+        let msg = self.current.as_ref().map(|job| &job.msg);
+        let Ok(res) = match response {
+            Response::Received(word) => {
+                self.driver
+                    .process(word, &mut self.tx, &self.clock, msg, is_low_latency)
+            }
+            response => self
+                .driver
+                .process_response(response, &mut self.tx, &self.clock, msg),
+        };
+
+        if let Some(delay) = self.clock.delay.take() {
+            time::sleep(delay).await;
+        }
+        self.flush().await?;
+
+        if let Some(outcome) = res.outcome() {
+            if let Some(job) = self.current.take() {
+                let _ = job.done.send(outcome);
+            }
+        }
+        let vet = match res {
+            ProcessResult::VetReply { timeout_ms } => Some(timeout_ms),
+            _ => None,
+        };
+        // no subscribers is fine
+        let _ = self.handle.events.send(Arc::new(res));
+        self.sniff(response);
+
+        Ok(vet)
+    }
+
+    fn sniff(&mut self, response: Response) {
+        let Some(sniffer) = &mut self.sniffer else {
+            return;
+        };
+        // the address of the winner is on the bus
+        let (Response::Received(word) | Response::Started(word) | Response::Failed(word)) =
+            response
+        else {
+            return;
+        };
+
+        let Ok(res) = sniffer.process(word, &mut Silent, &self.clock, None, false);
+        if !res.is_none() {
+            let _ = self.handle.events.send(Arc::new(res));
+        }
+    }
+
+    async fn flush(&mut self) -> io::Result<()> {
+        if !self.tx.buf.is_empty() {
+            self.io.write_all(&self.tx.buf).await?;
+            self.io.flush().await?;
+            self.tx.buf.clear();
+        }
+
+        Ok(())
+    }
+}
+
+/// The sniffer never transmits
+struct Silent;
+
+impl Transmit for Silent {
+    type Error = Infallible;
+
+    fn transmit_raw(&mut self, _bytes: &[u8]) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn clear_buffer(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct TxBuffer {
+    buf: Vec<u8>,
+    /// Encode as enhanced protocol commands
+    enhanced: bool,
+}
+
+impl TxBuffer {
+    fn push(&mut self, command: Command) {
+        self.buf.extend_from_slice(command.encode(&mut [0; 2]));
+    }
+}
+
+impl Transmit for TxBuffer {
+    type Error = Infallible;
+
+    fn transmit_raw(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        if self.enhanced {
+            bytes
+                .iter()
+                .for_each(|&byte| self.push(Command::Send(byte)));
+        } else {
+            self.buf.extend_from_slice(bytes);
+        }
+
+        Ok(())
+    }
+
+    fn clear_buffer(&mut self) -> Result<(), Self::Error> {
+        self.buf.clear();
+
+        Ok(())
+    }
+
+    fn transmit_start(&mut self, src: u8) -> Result<(), Self::Error> {
+        self.push(Command::Start(src));
+
+        Ok(())
+    }
+}
@@ -9,21 +9,70 @@
 #![doc = include_str!("../examples/integration.rs")]
 //! ```
 
+#[cfg(feature = "std")]
+extern crate std;
+
+#[macro_use]
+mod fmt;
+
 use core::{fmt::Debug, time::Duration};
 
-pub use crc::Crc;
-pub use telegram::{Buffer, MasterTelegram, Telegram, TelegramFlag, TelegramFlags};
+use codec::CodecError;
+use enhanced::Response;
+use event::EventQueue;
+use service::Identification;
+use stats::inc;
 
+pub use address::{Address, AddressKind, InvalidAddress};
+pub use clock::Clock;
+#[cfg(feature = "std")]
+pub use clock::StdClock;
+pub use crc::Crc;
+pub use event::{BusEvent, EVENT_QUEUE_LEN};
+pub use stats::Stats;
+pub use telegram::{Buffer, MasterTelegram, Telegram, TelegramFlag, TelegramFlags, Transaction};
+
+mod address;
+#[cfg(feature = "async")]
+pub mod asynch;
+mod clock;
+pub mod codec;
 mod crc;
+pub mod datetime;
+pub mod enhanced;
+mod event;
+#[cfg(feature = "tokio")]
+pub mod host;
+#[cfg(feature = "std")]
+pub mod pcap;
+pub mod queue;
+#[cfg(feature = "std")]
+pub mod record;
+pub mod scan;
+pub mod service;
+#[cfg(feature = "sim")]
+pub mod sim;
+mod stats;
 mod telegram;
 
 pub const MAX_BUF_U8: u8 = 32;
 pub const MAX_BUF: usize = MAX_BUF_U8 as usize;
 
+/// Destination address of broadcast telegrams
+pub const BROADCAST_ADDR: u8 = 0xFE;
+
 const SYN: u8 = 0xAA;
 const ACK_OK: u8 = 0x00;
 const ACK_ERR: u8 = 0xFF;
 const ESCAPE_PREFIX: u8 = 0xA9;
+/// The spec allows exactly one repetition after a negative acknowledge
+const MAX_ATTEMPTS: u8 = 2;
+
+/// Idle time after which the AUTO-SYN generator sends a SYN
+pub const AUTO_SYN_INTERVAL: Duration = Duration::from_millis(50);
+/// How often drivers check whether to send AUTO-SYN while the bus is idle
+#[cfg(any(feature = "async", feature = "tokio"))]
+const AUTO_SYN_POLL: Duration = Duration::from_millis(5);
 
 pub struct EbusDriver {
     crc_poly_telegram: u8,
@@ -36,6 +85,35 @@ pub struct EbusDriver {
     /// Allows bus access if 0, gets reset to FAIRNESS_MAX after successful access.
     fairness_counter: u8,
     fairness_max: u8,
+    /// Number of times the current telegram / reply has been sent or received
+    attempts: u8,
+    /// Own master address, master-master telegrams to it are acknowledged automatically
+    master_addr: Option<u8>,
+    /// Own slave address (master address + 5), only requests to it are reported
+    slave_addr: Option<u8>,
+    /// Report telegrams for other participants as `ProcessResult::Observed`
+    observe: bool,
+    /// Encoded answer to identification requests for our slave address
+    identification: Option<Buffer>,
+    /// Passive mode: never transmit, report every transaction on the bus
+    sniffing: bool,
+    /// Act as AUTO-SYN generator
+    auto_syn: Option<AutoSyn>,
+    /// Arbitration is done by an enhanced protocol adapter
+    adapter: Option<AdapterArbitration>,
+    /// Time the last byte was received
+    last_rx: Option<Duration>,
+    /// Time until which a reply is vetted, see `ProcessResult::VetReply`
+    vet_deadline: Option<Duration>,
+    /// Time of the SYN starting our current transaction
+    started: Option<Duration>,
+    /// Duration of our last transaction
+    latency: Option<Duration>,
+    /// Telegram reported as `InvalidSource`, not reported again while it is offered
+    rejected: Option<Telegram>,
+    stats: Stats,
+    /// Not yet taken with `drain_events`
+    events: EventQueue,
     state: State,
 }
 
@@ -50,6 +128,21 @@ impl EbusDriver {
             flags: Default::default(),
             fairness_counter: fairness_max,
             fairness_max,
+            attempts: 0,
+            master_addr: None,
+            slave_addr: None,
+            observe: false,
+            identification: None,
+            sniffing: false,
+            auto_syn: None,
+            adapter: None,
+            last_rx: None,
+            vet_deadline: None,
+            started: None,
+            latency: None,
+            rejected: None,
+            stats: Stats::default(),
+            events: EventQueue::default(),
             state: State::Start,
             crc_poly_telegram,
             crc_poly_data,
@@ -57,27 +150,366 @@ impl EbusDriver {
         }
     }
 
+    /// Set our own master address, the slave address is derived from it (master + 5).
+    ///
+    /// Master-master telegrams addressed to the master address are acknowledged by the driver
+    /// and reported as `ProcessResult::MasterMessage`. `ProcessResult::Request` is only
+    /// reported for telegrams addressed to the slave address.
+    /// Without an address, every telegram (including master-master) is reported as request
+    /// and has to be answered with `reply_as_slave` / `reply_ack`.
+    ///
+    /// Fails and keeps the previous address if `addr` is not one of the 25 master addresses.
+    pub fn set_master_address(&mut self, addr: u8) -> Result<(), InvalidAddress> {
+        let addr = Address(addr);
+        let slave = addr.slave_of_master().ok_or(InvalidAddress(addr))?;
+        self.master_addr = Some(addr.0);
+        self.slave_addr = Some(slave.0);
+
+        Ok(())
+    }
+
+    pub fn master_address(&self) -> Option<u8> {
+        self.master_addr
+    }
+
+    pub fn slave_address(&self) -> Option<u8> {
+        self.slave_addr
+    }
+
+    /// Report telegrams not addressed to us as `ProcessResult::Observed` instead of
+    /// silently dropping them. Has no effect unless an own address is set.
+    pub fn set_observe(&mut self, observe: bool) {
+        self.observe = observe;
+    }
+
+    /// Answer identification requests (0x0704) to our slave address automatically.
+    ///
+    /// Requires an own address (see `set_master_address`). Such requests are then
+    /// not reported, only the resulting `SlaveAckOk` / `SlaveAckErr`.
+    pub fn set_identification(
+        &mut self,
+        identification: Option<&Identification>,
+    ) -> Result<(), CodecError> {
+        self.identification = identification.map(Identification::to_buffer).transpose()?;
+
+        Ok(())
+    }
+
+    /// Enable or disable passive sniffing.
+    ///
+    /// While sniffing, the driver never transmits. Instead of `Request` / `Broadcast`,
+    /// every transaction on the bus is reported as `ProcessResult::Transaction`
+    /// once the closing SYN has been received.
+    pub fn set_sniffing(&mut self, sniffing: bool) {
+        self.sniffing = sniffing;
+        self.reset_wait_syn();
+    }
+
+    pub fn is_sniffing(&self) -> bool {
+        self.sniffing
+    }
+
+    /// Act as AUTO-SYN generator, see [`EbusDriver::poll`]
+    pub fn set_auto_syn(&mut self, enabled: bool) {
+        self.auto_syn = enabled.then(AutoSyn::default);
+    }
+
+    pub fn is_auto_syn_enabled(&self) -> bool {
+        self.auto_syn.is_some()
+    }
+
+    /// `true` if we are the AUTO-SYN generator and no other generator has been detected
+    pub fn is_auto_syn_active(&self) -> bool {
+        self.auto_syn.as_ref().is_some_and(|gen| !gen.backed_off)
+    }
+
+    /// Let the adapter do the arbitration, see [`enhanced`].
+    ///
+    /// Instead of sending our source address after SYN, `Transmit::transmit_start` is
+    /// called and the outcome is expected as [`Response::Started`] or [`Response::Failed`]
+    /// through [`EbusDriver::process_response`].
+    pub fn set_adapter_arbitration(&mut self, enabled: bool) {
+        self.adapter = enabled.then(AdapterArbitration::default);
+    }
+
+    pub fn is_adapter_arbitration(&self) -> bool {
+        self.adapter.is_some()
+    }
+
+    /// Handle timeouts, call this regularly while no byte arrives (e.g. when reading from
+    /// UART times out).
+    ///
+    /// Acknowledges a reply once the vetting timeout has passed (see
+    /// `ProcessResult::VetReply`) and, as AUTO-SYN generator, sends SYN once the bus has been
+    /// idle for [`AUTO_SYN_INTERVAL`]. Bytes sent come back like any other byte and have to
+    /// be passed to `process` as usual, so our own telegrams are sent after our SYN.
+    ///
+    /// If SYN from another generator is received (one we did not send, on an idle bus), the
+    /// generator backs off and only takes over again after the bus has been idle for twice
+    /// the [`AUTO_SYN_INTERVAL`]. SYNs closing a telegram do not count.
+    pub fn poll<T: Transmit>(
+        &mut self,
+        transmit: &mut T,
+        clock: &impl Clock,
+    ) -> Result<(), T::Error> {
+        let now = clock.now();
+        if self.vet_deadline.is_some_and(|deadline| now >= deadline)
+            && matches!(self.state, State::VetReply { .. })
+        {
+            self.vet_timeout(transmit)?;
+        }
+
+        let Some(gen) = &mut self.auto_syn else {
+            return Ok(());
+        };
+        let interval = if gen.backed_off {
+            2 * AUTO_SYN_INTERVAL
+        } else {
+            AUTO_SYN_INTERVAL
+        };
+        let idle = self
+            .last_rx
+            .map_or(Duration::MAX, |last| now.saturating_sub(last));
+        if self.sniffing || gen.pending || idle < interval {
+            return Ok(());
+        }
+
+        if gen.backed_off {
+            info!("no AUTO-SYN on the bus, taking over");
+            gen.backed_off = false;
+        }
+        gen.pending = true;
+        transmit.transmit_syn()
+    }
+
+    /// Time the last byte was received, according to the clock passed to `process`
+    pub fn last_rx(&self) -> Option<Duration> {
+        self.last_rx
+    }
+
+    /// Duration of the last telegram we sent, from the SYN we started arbitrating at to
+    /// the final result (e.g. `MasterAckOk` or `Reply`)
+    pub fn last_latency(&self) -> Option<Duration> {
+        self.latency
+    }
+
+    /// Counters of bus events since creation or the last [`EbusDriver::reset_stats`]
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = Stats::default();
+    }
+
+    /// Take the protocol violations and other unusual conditions seen by `process` and
+    /// `poll` since the last call, oldest first.
+    ///
+    /// Only the last [`EVENT_QUEUE_LEN`] events are kept, so drain them regularly, e.g. after
+    /// every `process`.
+    pub fn drain_events(&mut self) -> impl Iterator<Item = BusEvent> + '_ {
+        core::iter::from_fn(|| self.events.pop())
+    }
+
+    /// `true` from arbitrating for the bus until the outcome of our telegram is reported,
+    /// including vetting and acknowledging the reply
+    pub fn is_sending(&self) -> bool {
+        self.state.is_acquiring()
+            || self.state.has_bus_lock()
+            || matches!(
+                self.state,
+                State::VetReply { .. } | State::VetSuccess { .. }
+            )
+            || self.adapter_requested().is_some()
+    }
+
     /// Indicates whether the next byte needs to be supplied with low (sub-ms) latency
     pub fn is_time_critical(&self) -> bool {
         // return `true` for states where a SYN symbol is likely to arrive soon
         matches!(
             self.state,
-            State::Unknown | State::AcquiringLock | State::Replied
+            State::Unknown | State::AcquiringLock | State::Replied { .. }
         )
     }
 
+    /// Process a byte received at `clock.now()`.
+    ///
+    /// `clock` also provides the arbitration delay.
     pub fn process<T: Transmit>(
         &mut self,
         word: u8,
         transmit: &mut T,
-        sleep: impl Fn(Duration),
+        clock: &impl Clock,
+        next_msg: Option<&MasterTelegram>,
+        is_low_latency: bool,
+    ) -> Result<ProcessResult, T::Error> {
+        let now = clock.now();
+        let gap = self.last_rx.map(|last| now.saturating_sub(last));
+        self.last_rx = Some(now);
+        self.vet_deadline = None;
+
+        if word != SYN
+            && gap.is_some_and(|gap| gap > AUTO_SYN_INTERVAL)
+            && !matches!(self.state, State::Start | State::Unknown)
+        {
+            // AUTO-SYN should have come in the meantime, we are out of sync
+            warn!("gap of {:?} within a telegram", gap);
+            if let Some(gap) = gap {
+                self.events.push(BusEvent::Gap { gap });
+            }
+            let was_timeout = self.state.master_is_awaiting();
+            self.reset_wait_syn();
+            self.started = None;
+            if was_timeout {
+                inc(&mut self.stats.timeouts);
+            }
+
+            return Ok(if was_timeout {
+                ProcessResult::Timeout
+            } else {
+                ProcessResult::None
+            });
+        }
+
+        if word == SYN {
+            self.check_auto_syn(gap);
+        }
+
+        let requested = self.adapter_requested();
+        let res = self.process_word(word, transmit, clock, next_msg, is_low_latency)?;
+        match &res {
+            ProcessResult::VetReply { timeout_ms } => {
+                self.vet_deadline = Some(now + Duration::from_millis(*timeout_ms as u64));
+            }
+            res if res.outcome().is_some() => {
+                self.latency = self.started.take().map(|started| now - started);
+            }
+            _ => {}
+        }
+        if word == SYN
+            && (self.state.is_acquiring()
+                || requested.is_none() && self.adapter_requested().is_some())
+        {
+            self.started = Some(now);
+        }
+
+        Ok(res)
+    }
+
+    /// Process a response of an enhanced protocol adapter, see
+    /// [`EbusDriver::set_adapter_arbitration`]
+    pub fn process_response<T: Transmit>(
+        &mut self,
+        response: Response,
+        transmit: &mut T,
+        clock: &impl Clock,
+        next_msg: Option<&MasterTelegram>,
+    ) -> Result<ProcessResult, T::Error> {
+        match response {
+            Response::Received(word) => self.process(word, transmit, clock, next_msg, true),
+            Response::Started(src) => {
+                if self.take_adapter_requested() != Some(src) {
+                    warn!("adapter started arbitration for 0x{:X} unasked", src);
+                    return Ok(ProcessResult::None);
+                }
+                if next_msg.is_none_or(|msg| msg.telegram.src != src) {
+                    // nothing to send anymore, give the bus back
+                    warn!("won arbitration without a telegram to send");
+                    self.transmit_syn(transmit)?;
+                    self.reset_wait_syn();
+                    return Ok(ProcessResult::None);
+                }
+
+                // the adapter has sent our address, continue as if it came back
+                self.state = State::AcquiringLock;
+                self.process(src, transmit, clock, next_msg, true)
+            }
+            Response::Failed(winner) => {
+                if let Some(src) = self.take_adapter_requested() {
+                    info!("failed to acquire lock");
+                    inc(&mut self.stats.arbitration_lost);
+                    self.events.push(BusEvent::LostArbitration { winner });
+                    if winner & 0x0F != src & 0x0F {
+                        self.fairness_counter = 2;
+                    }
+                }
+
+                // the winner's address is the first byte of its telegram
+                self.process(winner, transmit, clock, next_msg, true)
+            }
+            Response::Resetted { .. } => {
+                self.take_adapter_requested();
+                self.reset_wait_syn();
+                Ok(ProcessResult::None)
+            }
+            Response::ErrorEbus(code) | Response::ErrorHost(code) => {
+                warn!("adapter error {:?}", response);
+                let host = matches!(response, Response::ErrorHost(_));
+                self.events.push(BusEvent::AdapterError { code, host });
+                self.take_adapter_requested();
+                self.reset_wait_syn();
+                Ok(ProcessResult::None)
+            }
+            Response::Info(_) => Ok(ProcessResult::None),
+        }
+    }
+
+    /// Back off if a SYN we did not send arrives on an idle bus.
+    ///
+    /// SYN right after another SYN can only come from a generator, after a telegram only if
+    /// its master did not close it right away.
+    fn check_auto_syn(&mut self, gap: Option<Duration>) {
+        let Some(gen) = &mut self.auto_syn else {
+            return;
+        };
+        if core::mem::take(&mut gen.pending) || gen.backed_off {
+            return;
+        }
+
+        let idle = match self.state {
+            State::Start => true,
+            State::Unknown => gap.is_some_and(|gap| gap >= AUTO_SYN_INTERVAL / 2),
+            _ => false,
+        };
+        if idle {
+            info!("other AUTO-SYN generator detected, backing off");
+            gen.backed_off = true;
+            self.events.push(BusEvent::OtherAutoSyn);
+        }
+    }
+
+    /// Send SYN without taking it for another AUTO-SYN generator when it comes back
+    fn transmit_syn<T: Transmit>(&mut self, transmit: &mut T) -> Result<(), T::Error> {
+        if let Some(gen) = &mut self.auto_syn {
+            gen.pending = true;
+        }
+
+        transmit.transmit_syn()
+    }
+
+    /// Source address the adapter is arbitrating for
+    fn adapter_requested(&self) -> Option<u8> {
+        self.adapter.as_ref().and_then(|adapter| adapter.requested)
+    }
+
+    fn take_adapter_requested(&mut self) -> Option<u8> {
+        self.adapter
+            .as_mut()
+            .and_then(|adapter| adapter.requested.take())
+    }
+
+    fn process_word<T: Transmit>(
+        &mut self,
+        word: u8,
+        transmit: &mut T,
+        clock: &impl Clock,
         next_msg: Option<&MasterTelegram>,
         is_low_latency: bool,
     ) -> Result<ProcessResult, T::Error> {
         /*
          * High level description of how the code is structured:
          *
-         * `process` tries to handle the time critical case as fast as possible:
+         * `process_word` tries to handle the time critical case as fast as possible:
          * sending our source address after a SYN (if we are allowd to send and there is a msg in queue)
          *
          * The locking of the bus will always be started from within this function.
@@ -90,27 +522,65 @@ impl EbusDriver {
             transmit.clear_buffer()?;
 
             let was_timeout = self.state.master_is_awaiting();
-
-            if self.process_syn() && is_low_latency && next_msg.is_some() {
+            let transaction = self.state.take_transaction();
+            // arbitrating with anything but a master address would wedge `AcquiringLock`
+            let invalid_src = next_msg.is_some_and(|msg| !Address(msg.telegram.src).is_master());
+
+            if self.process_syn()
+                && !self.sniffing
+                // the adapter does not depend on our timing
+                && (is_low_latency || self.adapter.is_some())
+                && next_msg.is_some()
+                && !invalid_src
+            {
                 #[allow(clippy::unnecessary_unwrap)]
                 let msg = next_msg.unwrap();
                 let src = msg.telegram.src;
 
-                sleep(self.arbitration_delay);
-                transmit.transmit_encode(&[src])?;
-                self.state = State::AcquiringLock;
+                if let Some(adapter) = &mut self.adapter {
+                    if adapter.requested.is_none() {
+                        transmit.transmit_start(src)?;
+                        adapter.requested = Some(src);
+                    }
+                    // listen until the adapter reports the outcome
+                    self.reset_syn();
+                } else {
+                    clock.delay(self.arbitration_delay);
+                    transmit.transmit_encode(&[src])?;
+                    self.state = State::AcquiringLock;
+                }
             } else {
                 self.reset_syn();
             }
 
             self.flags.remove(Flag::WasEscapePrefix);
 
-            if was_timeout {
+            if let Some(transaction) = transaction {
+                Ok(ProcessResult::Transaction { transaction })
+            } else if was_timeout {
+                inc(&mut self.stats.timeouts);
                 Ok(ProcessResult::Timeout)
+            } else if let Some(msg) = next_msg.filter(|_| invalid_src) {
+                if self.rejected.as_ref() == Some(&msg.telegram) {
+                    // reported at a previous SYN already
+                    return Ok(ProcessResult::None);
+                }
+                warn!("refusing to send telegram with non-master source address");
+                self.events.push(BusEvent::InvalidSource {
+                    src: msg.telegram.src,
+                });
+                self.rejected = Some(msg.telegram.clone());
+                Ok(ProcessResult::InvalidSource)
             } else {
+                self.rejected = None;
                 Ok(ProcessResult::None)
             }
         } else {
+            if let Some(gen) = &mut self.auto_syn {
+                // our SYN got lost in a collision
+                gen.pending = false;
+            }
+
             self.process_slow(word, transmit, next_msg)
         }
     }
@@ -123,19 +593,20 @@ impl EbusDriver {
         _token: RequestToken,
     ) -> Result<(), T::Error> {
         if data.len() > MAX_BUF {
-            #[cfg(feature = "log")]
-            log::warn!("replying with more than MAX_BUF bytes");
+            warn!("replying with more than MAX_BUF bytes");
         }
 
         let mut counter = 0;
         counter += transmit.transmit_encode(&[ACK_OK])?;
-
-        let mut crc = Crc::new(self.crc_poly_telegram);
-        counter += transmit.transmit_encode_with_crc(&[data.len() as u8], &mut crc)?;
-        counter += transmit.transmit_encode_with_crc(data, &mut crc)?;
-        counter += transmit.transmit_encode(&[crc.calc_crc()])?;
-
-        self.state = State::ReplyLoopback { expect: counter };
+        counter += self.send_reply(transmit, data)?;
+
+        // keep the reply in case the master asks for a repetition
+        let reply = Buffer::from_slice(&data[..data.len().min(MAX_BUF)]);
+        self.attempts = 1;
+        self.state = State::ReplyLoopback {
+            expect: counter,
+            reply: Some(reply),
+        };
 
         Ok(())
     }
@@ -148,7 +619,11 @@ impl EbusDriver {
         let mut counter = 0;
         counter += transmit.transmit_encode(&[ACK_OK])?;
 
-        self.state = State::ReplyLoopback { expect: counter };
+        self.attempts = 1;
+        self.state = State::ReplyLoopback {
+            expect: counter,
+            reply: None,
+        };
 
         Ok(())
     }
@@ -156,17 +631,19 @@ impl EbusDriver {
     /// Returns `true` if we may lock the bus
     fn process_syn(&mut self) -> bool {
         if self.state.has_bus_lock() {
-            #[cfg(feature = "log")]
-            log::info!("timeout: SYN while holding bus lock");
+            info!("timeout: SYN while holding bus lock");
+            self.events.push(BusEvent::SynWhileLocked);
             self.reset_syn();
         } else if self.state.is_acquiring() {
-            #[cfg(feature = "log")]
-            log::warn!("unexpected double SYN prevented lock");
+            warn!("unexpected double SYN prevented lock");
+            inc(&mut self.stats.double_syn);
+            self.events.push(BusEvent::DoubleSyn);
             self.reset_syn();
         } else if self.is_allowed_to_lock() {
             return true;
         } else {
             self.fairness_counter -= 1;
+            inc(&mut self.stats.fairness_waits);
         }
 
         false
@@ -186,8 +663,7 @@ impl EbusDriver {
             crc.add(word);
         }
 
-        #[cfg(feature = "log")]
-        log::debug!("word: {word:X}, state: {:?}", self.state);
+        debug!("word: {:X}, state: {:?}", word, self.state);
 
         if self.flags.check_remove(Flag::WasEscapePrefix) {
             if word == 0x00 {
@@ -195,8 +671,9 @@ impl EbusDriver {
             } else if word == 0x01 {
                 word = SYN;
             } else {
-                #[cfg(feature = "log")]
-                log::warn!("detected invalid escape sequence");
+                warn!("detected invalid escape sequence");
+                inc(&mut self.stats.invalid_escape);
+                self.events.push(BusEvent::InvalidEscape { byte: word });
                 self.reset_wait_syn();
 
                 return Ok(ProcessResult::None);
@@ -218,18 +695,21 @@ impl EbusDriver {
             State::AcquiringLock => {
                 let msg = msg.unwrap();
                 if word == msg.telegram.src {
+                    inc(&mut self.stats.arbitration_won);
                     let expect = self.send_data(transmit, msg)?;
+                    self.attempts = 1;
                     self.state = State::DataLoopback { expect };
                 } else {
                     let prio_class = word & 0x0F;
                     let own_prio = msg.telegram.src & 0x0F;
+                    inc(&mut self.stats.arbitration_lost);
+                    self.events.push(BusEvent::LostArbitration { winner: word });
 
                     if prio_class == own_prio {
                         // instantly try again on next SYN
                         self.state = State::Unknown;
                     } else {
-                        #[cfg(feature = "log")]
-                        log::info!("failed to acquire lock");
+                        info!("failed to acquire lock");
                         self.fairness_counter = 2;
                         self.state = State::GotSrc { src: word };
                     }
@@ -238,38 +718,65 @@ impl EbusDriver {
             State::DataLoopback { expect } => {
                 *expect -= 1;
                 if *expect == 0 {
+                    let msg = msg.unwrap();
+                    if msg.telegram.dest == BROADCAST_ADDR {
+                        // broadcasts are not acknowledged
+                        self.success(transmit)?;
+
+                        return Ok(ProcessResult::BroadcastSent);
+                    }
+
                     self.state = State::AwaitingAck;
                 }
             }
             State::AwaitingAck => match word {
                 ACK_OK => {
                     let msg = msg.unwrap();
-                    if msg.flags & TelegramFlag::ExpectReply {
+                    // masters never reply, they just acknowledge
+                    if msg.flags & TelegramFlag::ExpectReply
+                        && !Address(msg.telegram.dest).is_master()
+                    {
+                        // from now on, count the attempts of the slave reply
+                        self.attempts = 1;
                         self.state = State::AwaitingLen;
                     } else {
+                        let attempts = self.attempts;
                         self.success(transmit)?;
 
-                        return Ok(ProcessResult::MasterAckOk);
+                        return Ok(ProcessResult::MasterAckOk { attempts });
                     }
                 }
                 x => {
-                    #[cfg(feature = "log")]
-                    log::warn!("telegram not acknowledged");
+                    warn!("telegram not acknowledged");
+                    inc(&mut self.stats.nacks);
                     if x != ACK_ERR {
-                        #[cfg(feature = "log")]
-                        log::warn!("expected ack, got non-ack byte: 0x{word:X}");
+                        warn!("expected ack, got non-ack byte: 0x{:X}", word);
                     }
-                    self.reset_wait_syn();
+                    self.events.push(BusEvent::TelegramNotAcked { byte: x });
+
+                    if self.attempts < MAX_ATTEMPTS {
+                        // repeat the whole telegram without giving up the lock
+                        let msg = msg.unwrap();
+                        let mut expect = transmit.transmit_encode(&[msg.telegram.src])?;
+                        expect += self.send_data(transmit, msg)?;
+                        self.attempts += 1;
+                        self.state = State::DataLoopback { expect };
+                    } else {
+                        let attempts = self.attempts;
+                        self.reset_wait_syn();
 
-                    return Ok(ProcessResult::MasterAckErr);
+                        return Ok(ProcessResult::MasterAckErr { attempts });
+                    }
                 }
             },
             State::AwaitingLen => {
                 if word > MAX_BUF_U8 {
-                    #[cfg(feature = "log")]
-                    log::warn!("got slave response with len > MAX_BUF");
+                    warn!("got slave response with len > MAX_BUF");
+                    inc(&mut self.stats.len_overflow);
+                    self.events.push(BusEvent::ReplyLenOverflow { len: word });
                     self.reset_wait_syn();
-                    // TODO: how to handle?
+
+                    return Ok(ProcessResult::ReplyLenOverflow { len: word });
                 }
 
                 // TODO: handle 0 len case?
@@ -318,10 +825,21 @@ impl EbusDriver {
 
                     return Ok(ProcessResult::VetReply { timeout_ms: 6 });
                 } else {
-                    #[cfg(feature = "log")]
-                    log::warn!("got crc 0x{word:X}, expected 0x{crc_should:X}");
+                    warn!("got crc 0x{:X}, expected 0x{:X}", word, crc_should);
+                    inc(&mut self.stats.reply_crc_errors);
+                    self.events.push(BusEvent::ReplyCrcError {
+                        expected: crc_should,
+                        received: word,
+                    });
                     transmit.transmit_raw(&[ACK_ERR])?;
 
+                    if self.attempts < MAX_ATTEMPTS {
+                        // the slave repeats its reply after our NACK
+                        self.attempts += 1;
+                        self.state = State::NackLoopback;
+                        return Ok(ProcessResult::None);
+                    }
+
                     self.state = State::Unknown;
 
                     //self.success(transmit)?;
@@ -329,34 +847,43 @@ impl EbusDriver {
                 }
             }
             State::VetReply { data } => {
-                #[cfg(feature = "log")]
-                log::info!(
-                    "reply vetting: got byte 0x{} when we expected end of message",
+                info!(
+                    "reply vetting: got byte 0x{:X} when we expected end of message",
                     word
                 );
+                self.events.push(BusEvent::VetInterrupted { byte: word });
 
                 let data = data.clone();
                 self.state = State::Unknown;
-                return Ok(ProcessResult::Reply { data, clean: false });
+                return Ok(ProcessResult::Reply {
+                    data,
+                    clean: false,
+                    attempts: self.attempts,
+                });
             }
             State::VetSuccess { data } => {
                 if word != ACK_OK {
-                    #[cfg(feature = "log")]
-                    log::warn!(
+                    warn!(
                         "reply vetting: expected to hear back our own ACK OK (00), but got 0x{:X}",
                         word
                     );
+                    self.events.push(BusEvent::OwnAckCorrupted { byte: word });
                 }
 
                 let res = Ok(ProcessResult::Reply {
                     data: data.clone(),
                     clean: true,
+                    attempts: self.attempts,
                 });
 
                 self.success(transmit)?;
 
                 return res;
             }
+            State::NackLoopback => {
+                // this is our own ACK_ERR, the repeated reply follows
+                self.state = State::AwaitingLen;
+            }
             // === slave states ===
             State::GotSrc { src } => {
                 self.state = State::GotDst {
@@ -382,19 +909,36 @@ impl EbusDriver {
                 let mut len = word;
 
                 if len > MAX_BUF_U8 {
-                    #[cfg(feature = "log")]
-                    log::warn!("receiving master telegram with len > {MAX_BUF_U8}");
+                    warn!("receiving master telegram with len > {}", MAX_BUF_U8);
+                    inc(&mut self.stats.len_overflow);
+                    self.events.push(BusEvent::TelegramLenOverflow { len });
                     len = MAX_BUF_U8;
                 }
 
-                self.state = State::ReceivingTelegram {
-                    src: *src,
-                    dst: *dst,
-                    svc: *svc,
-                    len,
-                    cursor: 0,
-                    buf: [0; MAX_BUF],
-                };
+                if len == 0 {
+                    // no data, the CRC follows right away
+                    self.state = State::ReceivingTelegramCrc {
+                        src: *src,
+                        dst: *dst,
+                        svc: *svc,
+                        len,
+                        buf: [0; MAX_BUF],
+                        crc: Crc::new(self.crc_poly_telegram)
+                            .add_decoded(&[*src, *dst])
+                            .add_decoded(&svc.to_be_bytes())
+                            .add_decoded(&[len])
+                            .calc_crc(),
+                    };
+                } else {
+                    self.state = State::ReceivingTelegram {
+                        src: *src,
+                        dst: *dst,
+                        svc: *svc,
+                        len,
+                        cursor: 0,
+                        buf: [0; MAX_BUF],
+                    };
+                }
             }
             State::ReceivingTelegram {
                 src,
@@ -437,7 +981,53 @@ impl EbusDriver {
                     service: *svc,
                     data: Buffer::from_parts(*buf, *len),
                 };
-                if *crc == word {
+                let crc = *crc;
+                inc(&mut self.stats.telegrams_seen);
+                if crc != word {
+                    inc(&mut self.stats.telegram_crc_errors);
+                    self.events.push(BusEvent::TelegramCrcError {
+                        expected: crc,
+                        received: word,
+                    });
+                }
+                if self.sniffing {
+                    let broadcast = telegram.dest == BROADCAST_ADDR;
+                    let transaction = Transaction {
+                        telegram,
+                        telegram_crc_ok: crc == word,
+                        slave_ack: None,
+                        reply: None,
+                        reply_crc_ok: None,
+                        master_ack: None,
+                    };
+                    self.state = if broadcast {
+                        State::SniffDone { transaction }
+                    } else {
+                        State::SniffAwaitingAck { transaction }
+                    };
+                } else if crc == word && telegram.dest == BROADCAST_ADDR {
+                    // no ACK follows a broadcast, master sends SYN next
+                    self.state = State::Unknown;
+                    return Ok(ProcessResult::Broadcast { telegram });
+                } else if crc == word && !self.is_for_us(telegram.dest) {
+                    // telegram for someone else, there is nothing for us to do
+                    self.state = State::Unknown;
+                    if self.observe {
+                        return Ok(ProcessResult::Observed { telegram });
+                    }
+                    return Ok(ProcessResult::None);
+                } else if crc == word && self.master_addr == Some(telegram.dest) {
+                    self.reply_ack(transmit, RequestToken { _priv: () })?;
+                    return Ok(ProcessResult::MasterMessage { telegram });
+                } else if crc == word
+                    && telegram.service == service::IDENTIFICATION
+                    && self.slave_addr == Some(telegram.dest)
+                    && self.identification.is_some()
+                {
+                    let answer = self.identification.clone().unwrap();
+                    self.reply_as_slave(answer.as_bytes(), transmit, RequestToken { _priv: () })?;
+                    return Ok(ProcessResult::None);
+                } else if crc == word {
                     let res = ProcessResult::Request {
                         telegram,
                         token: RequestToken { _priv: () },
@@ -445,38 +1035,165 @@ impl EbusDriver {
                     self.state = State::GotTelegram;
                     return Ok(res);
                 } else {
-                    #[cfg(feature = "log")]
-                    log::warn!("crc of {telegram:02X?} failed: expected 0x{crc:X}, got 0x{word:X}");
+                    warn!(
+                        "crc of {:?} failed: expected 0x{:X}, got 0x{:X}",
+                        telegram, crc, word
+                    );
                     self.state = State::Unknown;
                     return Ok(ProcessResult::TelegramCrcError);
                 }
             }
             State::GotTelegram => {
                 // we would have switched into ReplyLoopback if we sent a reply
-                // TODO: could sniff here
                 self.state = State::Unknown;
             }
-            State::ReplyLoopback { expect } => {
+            // === sniffer states ===
+            State::SniffAwaitingAck { transaction } => {
+                let mut transaction = transaction.clone();
+                transaction.slave_ack = Some(word);
+
+                if word != ACK_OK {
+                    // the master repeats its telegram
+                    self.state = State::Start;
+                    return Ok(ProcessResult::Transaction { transaction });
+                }
+
+                self.state = if Address(transaction.telegram.dest).is_master() {
+                    State::SniffDone { transaction }
+                } else {
+                    State::SniffAwaitingLen { transaction }
+                };
+            }
+            State::SniffAwaitingLen { transaction } => {
+                let mut transaction = transaction.clone();
+                let total = word;
+                if total > MAX_BUF_U8 {
+                    // the rest of the reply cannot be told apart from CRC and ACK
+                    warn!("sniffed slave response with len > MAX_BUF");
+                    inc(&mut self.stats.len_overflow);
+                    self.events.push(BusEvent::ReplyLenOverflow { len: word });
+                    transaction.reply_crc_ok = Some(false);
+                    self.state = State::Unknown;
+
+                    return Ok(ProcessResult::Transaction { transaction });
+                }
+
+                self.state = if total > 0 {
+                    State::SniffReceivingReply {
+                        transaction,
+                        buf: [0; MAX_BUF],
+                        cursor: 0,
+                        total,
+                    }
+                } else {
+                    State::SniffAwaitingCrc {
+                        transaction,
+                        buf: [0; MAX_BUF],
+                        len: 0,
+                    }
+                };
+            }
+            State::SniffReceivingReply {
+                transaction,
+                buf,
+                cursor,
+                total,
+            } => {
+                buf[*cursor as usize] = word;
+                *cursor += 1;
+
+                if *cursor >= *total {
+                    self.state = State::SniffAwaitingCrc {
+                        transaction: transaction.clone(),
+                        buf: *buf,
+                        len: *total,
+                    };
+                }
+            }
+            State::SniffAwaitingCrc {
+                transaction,
+                buf,
+                len,
+            } => {
+                let crc = Crc::new(self.crc_poly_telegram)
+                    .add_decoded(&[*len])
+                    .add_decoded(&buf[..*len as usize])
+                    .calc_crc();
+
+                let mut transaction = transaction.clone();
+                transaction.reply = Some(Buffer::from_parts(*buf, *len));
+                transaction.reply_crc_ok = Some(crc == word);
+                if crc != word {
+                    inc(&mut self.stats.reply_crc_errors);
+                    self.events.push(BusEvent::ReplyCrcError {
+                        expected: crc,
+                        received: word,
+                    });
+                }
+
+                self.state = State::SniffAwaitingMasterAck { transaction };
+            }
+            State::SniffAwaitingMasterAck { transaction } => {
+                let mut transaction = transaction.clone();
+                transaction.master_ack = Some(word);
+
+                if word != ACK_OK {
+                    // the slave repeats its reply
+                    let mut repeat = transaction.clone();
+                    repeat.reply = None;
+                    repeat.reply_crc_ok = None;
+                    repeat.master_ack = None;
+                    self.state = State::SniffAwaitingLen {
+                        transaction: repeat,
+                    };
+                    return Ok(ProcessResult::Transaction { transaction });
+                }
+
+                self.state = State::SniffDone { transaction };
+            }
+            State::SniffDone { .. } => {
+                debug!("sniffer: unexpected byte 0x{:X} before SYN", word);
+                self.events.push(BusEvent::UnexpectedByte { byte: word });
+            }
+            State::ReplyLoopback { expect, reply } => {
                 *expect -= 1;
                 if *expect == 0 {
-                    self.state = State::Replied;
+                    self.state = State::Replied {
+                        reply: reply.take(),
+                    };
                 }
             }
-            State::Replied => match word {
+            State::Replied { reply } => match word {
                 ACK_OK => {
+                    let attempts = self.attempts;
                     self.reset_wait_syn();
-                    return Ok(ProcessResult::SlaveAckOk);
+                    return Ok(ProcessResult::SlaveAckOk { attempts });
                 }
                 x => {
-                    #[cfg(feature = "log")]
-                    log::warn!("reply not acknowledged");
+                    warn!("reply not acknowledged");
+                    inc(&mut self.stats.nacks);
                     if x != ACK_ERR {
-                        #[cfg(feature = "log")]
-                        log::warn!("expected ack, got non-ack byte: 0x{word:X}");
+                        warn!("expected ack, got non-ack byte: 0x{:X}", word);
+                    }
+                    self.events.push(BusEvent::ReplyNotAcked { byte: x });
+
+                    match reply.take() {
+                        Some(reply) if self.attempts < MAX_ATTEMPTS => {
+                            // repeat our reply once (without ACK)
+                            let expect = self.send_reply(transmit, reply.as_bytes())?;
+                            self.attempts += 1;
+                            self.state = State::ReplyLoopback {
+                                expect,
+                                reply: Some(reply),
+                            };
+                        }
+                        _ => {
+                            let attempts = self.attempts;
+                            self.reset_wait_syn();
+
+                            return Ok(ProcessResult::SlaveAckErr { attempts });
+                        }
                     }
-                    self.reset_wait_syn();
-
-                    return Ok(ProcessResult::SlaveAckErr);
                 }
             },
         }
@@ -496,11 +1213,23 @@ impl EbusDriver {
         Ok(())
     }
 
+    /// Sends NN, the reply data and the CRC
+    fn send_reply<T: Transmit>(&self, transmit: &mut T, data: &[u8]) -> Result<u8, T::Error> {
+        let mut counter = 0;
+        let mut crc = Crc::new(self.crc_poly_telegram);
+        counter += transmit.transmit_encode_with_crc(&[data.len() as u8], &mut crc)?;
+        counter += transmit.transmit_encode_with_crc(data, &mut crc)?;
+        counter += transmit.transmit_encode(&[crc.calc_crc()])?;
+
+        Ok(counter)
+    }
+
     fn send_data<T: Transmit>(
         &mut self,
         transmit: &mut T,
         msg: &MasterTelegram,
     ) -> Result<u8, T::Error> {
+        inc(&mut self.stats.telegrams_sent);
         let mut tele_crc = Crc::new(self.crc_poly_telegram);
         tele_crc.add(msg.telegram.src);
         let mut counter = 0;
@@ -531,12 +1260,22 @@ impl EbusDriver {
         Ok(counter)
     }
 
+    /// Whether a telegram to `dst` is meant to be handled by us
+    fn is_for_us(&self, dst: u8) -> bool {
+        // without an own address, every request is ours
+        if Address(dst).is_master() {
+            self.master_addr.is_none_or(|addr| addr == dst)
+        } else {
+            self.slave_addr.is_none_or(|addr| addr == dst)
+        }
+    }
+
     fn is_allowed_to_lock(&self) -> bool {
         self.fairness_counter == 0
     }
 
     fn success<T: Transmit>(&mut self, transmit: &mut T) -> Result<(), T::Error> {
-        transmit.transmit_syn()?;
+        self.transmit_syn(transmit)?;
         self.flags.clear();
         // we do not reset to syn state, because we wait until we receive it (SYN) back
         self.state.reset_unknown();
@@ -559,6 +1298,7 @@ impl EbusDriver {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 enum State {
     /// We are waiting for next SYN
     Unknown,
@@ -583,6 +1323,8 @@ enum State {
         buf: [u8; MAX_BUF],
         len: u8,
     },
+    /// We sent ACK_ERR for a broken reply and wait for it to be echoed back
+    NackLoopback,
     // === slave states ===
     GotSrc {
         src: u8,
@@ -631,9 +1373,38 @@ enum State {
     ReplyLoopback {
         /// the number of bytes we expect to get echoed back (is counted down)
         expect: u8,
+        /// our reply, kept for a repetition (`None` for master-master)
+        reply: Option<Buffer>,
     },
     /// We are waiting to get ACK back.
-    Replied,
+    Replied {
+        reply: Option<Buffer>,
+    },
+    // === sniffer states ===
+    SniffAwaitingAck {
+        transaction: Transaction,
+    },
+    SniffAwaitingLen {
+        transaction: Transaction,
+    },
+    SniffReceivingReply {
+        transaction: Transaction,
+        buf: [u8; MAX_BUF],
+        cursor: u8,
+        total: u8,
+    },
+    SniffAwaitingCrc {
+        transaction: Transaction,
+        buf: [u8; MAX_BUF],
+        len: u8,
+    },
+    SniffAwaitingMasterAck {
+        transaction: Transaction,
+    },
+    /// Transaction is complete, waiting for SYN to report it
+    SniffDone {
+        transaction: Transaction,
+    },
 }
 
 impl State {
@@ -648,6 +1419,7 @@ impl State {
                 | State::AwaitingLen
                 | State::ReceivingReply { .. }
                 | State::AwaitingCrc { .. }
+                | State::NackLoopback
         )
     }
 
@@ -662,9 +1434,26 @@ impl State {
                 | Self::AwaitingCrc { .. }
                 | Self::AwaitingLen
                 | Self::ReceivingReply { .. }
+                | Self::NackLoopback
         )
     }
 
+    /// Takes the transaction collected so far out of a sniffer state
+    pub fn take_transaction(&mut self) -> Option<Transaction> {
+        match self.take() {
+            State::SniffAwaitingAck { transaction }
+            | State::SniffAwaitingLen { transaction }
+            | State::SniffReceivingReply { transaction, .. }
+            | State::SniffAwaitingCrc { transaction, .. }
+            | State::SniffAwaitingMasterAck { transaction }
+            | State::SniffDone { transaction } => Some(transaction),
+            other => {
+                *self = other;
+                None
+            }
+        }
+    }
+
     pub fn reset_unknown(&mut self) {
         *self = State::Unknown;
     }
@@ -675,35 +1464,78 @@ impl State {
 }
 
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+// not `Deserialize`, that would allow forging a `RequestToken`
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum ProcessResult {
     None,
-    /// We got a reply but would like to vet it for timeout ms. After that, call vet_timeout()
+    /// We got a reply but would like to vet it for timeout ms. After that, `poll` (or
+    /// `vet_timeout`) acknowledges it
     VetReply {
         timeout_ms: u16,
     },
     /// We replied as slave, master acknowledged
-    SlaveAckOk,
+    SlaveAckOk {
+        /// how often we had to send our reply (1 or 2)
+        attempts: u8,
+    },
     /// We replied as slave, master did not acknowledge
-    SlaveAckErr,
+    SlaveAckErr {
+        attempts: u8,
+    },
     /// We sent master-slave, slave acknowledged
-    MasterAckOk,
+    MasterAckOk {
+        /// how often we had to send our telegram (1 or 2)
+        attempts: u8,
+    },
     /// We sent master-slave, slave did not acknowledge
-    MasterAckErr,
+    MasterAckErr {
+        attempts: u8,
+    },
+    /// We sent a broadcast telegram (not acknowledged by design)
+    BroadcastSent,
     /// Expected recipient to send, but AUTO-SYN occurred
     Timeout,
+    /// The telegram to be sent has no valid master source address and will never be sent.
+    /// Only reported at the first SYN it is offered at
+    InvalidSource,
     /// CRC check of telegram failed (sent by another master)
     TelegramCrcError,
     /// CRC check of reply failed (sent by another slave)
     ReplyCrcError,
+    /// The slave announced a reply of more than `MAX_BUF` bytes, it was not received
+    ReplyLenOverflow {
+        len: u8,
+    },
+    /// Master-master telegram addressed to us, ACK has already been sent
+    MasterMessage {
+        telegram: Telegram,
+    },
+    /// Telegram for another participant (only reported if observing)
+    Observed {
+        telegram: Telegram,
+    },
     /// Master-slave request
     Request {
         telegram: Telegram,
+        /// Not serialized
+        #[cfg_attr(feature = "serde", serde(skip))]
         token: RequestToken,
     },
+    /// Transaction observed on the bus (sniffing only)
+    Transaction {
+        transaction: Transaction,
+    },
+    /// Broadcast telegram sent by another master
+    Broadcast {
+        telegram: Telegram,
+    },
     /// Slave sent reply
     Reply {
         data: Buffer,
         clean: bool,
+        /// how often the slave had to send its reply (1 or 2)
+        attempts: u8,
     },
 }
 
@@ -720,6 +1552,14 @@ impl ProcessResult {
         }
     }
 
+    pub fn as_broadcast(&self) -> Option<&Telegram> {
+        if let Self::Broadcast { telegram } = self {
+            Some(telegram)
+        } else {
+            None
+        }
+    }
+
     pub fn as_reply(&self) -> Option<&[u8]> {
         if let Self::Reply { data, .. } = self {
             Some(data.as_bytes())
@@ -729,7 +1569,78 @@ impl ProcessResult {
     }
 }
 
+/// Final outcome of a telegram we sent as master
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Outcome {
+    /// Recipient acknowledged, no reply was expected
+    Acked { attempts: u8 },
+    /// Slave replied
+    Reply { data: Buffer, attempts: u8 },
+    /// Broadcasts are not acknowledged
+    BroadcastSent,
+}
+
+/// Why a telegram we sent as master failed
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SendError {
+    /// Recipient did not acknowledge
+    Nack { attempts: u8 },
+    /// Recipient did not answer before AUTO-SYN
+    Timeout,
+    /// Reply CRC was wrong twice
+    ReplyCrcError,
+    /// Slave announced a reply longer than `MAX_BUF`
+    ReplyLenOverflow,
+    /// Telegram has no valid master source address
+    InvalidSource,
+}
+
+impl ProcessResult {
+    /// `Some` if this result finishes the telegram we are sending
+    pub fn outcome(&self) -> Option<Result<Outcome, SendError>> {
+        Some(match self {
+            Self::MasterAckOk { attempts } => Ok(Outcome::Acked {
+                attempts: *attempts,
+            }),
+            Self::Reply { data, attempts, .. } => Ok(Outcome::Reply {
+                data: data.clone(),
+                attempts: *attempts,
+            }),
+            Self::BroadcastSent => Ok(Outcome::BroadcastSent),
+            Self::MasterAckErr { attempts } => Err(SendError::Nack {
+                attempts: *attempts,
+            }),
+            Self::Timeout => Err(SendError::Timeout),
+            Self::ReplyCrcError => Err(SendError::ReplyCrcError),
+            Self::ReplyLenOverflow { .. } => Err(SendError::ReplyLenOverflow),
+            Self::InvalidSource => Err(SendError::InvalidSource),
+            _ => return None,
+        })
+    }
+}
+
+/// State of the arbitration done by an enhanced protocol adapter
+#[derive(Debug, Default)]
+struct AdapterArbitration {
+    /// START has been sent for this source address, STARTED / FAILED is pending
+    requested: Option<u8>,
+}
+
+/// State of the AUTO-SYN generator
+#[derive(Debug, Default)]
+struct AutoSyn {
+    /// Our SYN has been sent, but not received yet
+    pending: bool,
+    /// Another generator is active
+    backed_off: bool,
+}
+
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct RequestToken {
     _priv: (),
 }
@@ -784,6 +1695,11 @@ pub trait Transmit {
         self.transmit_raw(&[SYN])
     }
 
+    /// Ask an adapter to arbitrate for `src`, see [`EbusDriver::set_adapter_arbitration`]
+    fn transmit_start(&mut self, src: u8) -> Result<(), Self::Error> {
+        self.transmit_raw(&[src])
+    }
+
     #[doc(hidden)]
     fn _transmit_count(&mut self, bytes: &[u8]) -> Result<u8, Self::Error> {
         self.transmit_raw(bytes)
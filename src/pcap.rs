@@ -0,0 +1,278 @@
+//! Export bus traffic as pcapng for Wireshark (feature `std`).
+//!
+//! Telegrams are written to an interface with link type `USER0` (147), raw symbols to one
+//! with `USER1` (148). Every telegram gets a packet comment with the CRC and ACK verdicts.
+//! The dissector in `wireshark/ebus.lua` decodes both; put it into the Wireshark plugin
+//! folder or pass `-X lua_script:wireshark/ebus.lua`.
+//!
+//! Packets on `USER0` start with a flags byte, followed by the telegram without escapes
+//! and CRCs:
+//!
+//! ```text
+//! flags QQ ZZ PB SB NN DD.. [ACK] [NN DD..] [ACK]
+//! ```
+//!
+//! | bit | meaning                            |
+//! |-----|------------------------------------|
+//! | 0   | telegram CRC ok                    |
+//! | 1   | ACK of the recipient follows       |
+//! | 2   | reply follows                      |
+//! | 3   | reply CRC ok                       |
+//! | 4   | ACK of the master for the reply follows |
+
+use std::{
+    format, io,
+    string::String,
+    time::{SystemTime, UNIX_EPOCH},
+    vec,
+    vec::Vec,
+};
+
+use crate::{Address, ProcessResult, Telegram, Transaction, ACK_ERR, ACK_OK};
+
+/// Link type of decoded telegrams
+pub const LINKTYPE_TELEGRAM: u16 = 147;
+/// Link type of raw symbols
+pub const LINKTYPE_SYMBOLS: u16 = 148;
+
+pub const FLAG_TELEGRAM_CRC_OK: u8 = 1 << 0;
+pub const FLAG_SLAVE_ACK: u8 = 1 << 1;
+pub const FLAG_REPLY: u8 = 1 << 2;
+pub const FLAG_REPLY_CRC_OK: u8 = 1 << 3;
+pub const FLAG_MASTER_ACK: u8 = 1 << 4;
+
+const SECTION_HEADER: u32 = 0x0A0D_0D0A;
+const INTERFACE_DESCRIPTION: u32 = 0x0000_0001;
+const ENHANCED_PACKET: u32 = 0x0000_0006;
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+
+const OPT_END: u16 = 0;
+const OPT_COMMENT: u16 = 1;
+const OPT_IF_NAME: u16 = 2;
+const OPT_SHB_USERAPPL: u16 = 4;
+const OPT_EPB_FLAGS: u16 = 2;
+
+const TELEGRAM_INTERFACE: u32 = 0;
+const SYMBOL_INTERFACE: u32 = 1;
+
+/// Direction of raw symbols
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Direction {
+    /// Received from the bus
+    Inbound,
+    /// Transmitted by us
+    Outbound,
+}
+
+/// Writes a pcapng capture
+pub struct PcapWriter<W> {
+    inner: W,
+}
+
+impl<W: io::Write> PcapWriter<W> {
+    /// Write the section header and the interface descriptions
+    pub fn new(inner: W) -> io::Result<Self> {
+        let mut this = PcapWriter { inner };
+
+        let mut body = Vec::new();
+        body.extend(BYTE_ORDER_MAGIC.to_le_bytes());
+        body.extend(1u16.to_le_bytes());
+        body.extend(0u16.to_le_bytes());
+        // section length unknown
+        body.extend((-1i64).to_le_bytes());
+        push_option(&mut body, OPT_SHB_USERAPPL, b"ebus");
+        push_option(&mut body, OPT_END, b"");
+        this.block(SECTION_HEADER, &body)?;
+
+        for (linktype, name) in [(LINKTYPE_TELEGRAM, "ebus"), (LINKTYPE_SYMBOLS, "ebus-raw")] {
+            let mut body = Vec::new();
+            body.extend(linktype.to_le_bytes());
+            body.extend(0u16.to_le_bytes());
+            // no snapshot length limit
+            body.extend(0u32.to_le_bytes());
+            push_option(&mut body, OPT_IF_NAME, name.as_bytes());
+            push_option(&mut body, OPT_END, b"");
+            this.block(INTERFACE_DESCRIPTION, &body)?;
+        }
+
+        Ok(this)
+    }
+
+    /// Write a transaction observed while sniffing
+    pub fn write_transaction(
+        &mut self,
+        time: SystemTime,
+        transaction: &Transaction,
+    ) -> io::Result<()> {
+        let Transaction {
+            telegram,
+            telegram_crc_ok,
+            slave_ack,
+            reply,
+            reply_crc_ok,
+            master_ack,
+        } = transaction;
+
+        let mut flags = 0;
+        let mut data = Vec::new();
+        push_telegram(&mut data, telegram);
+        let mut verdicts = Vec::new();
+
+        if *telegram_crc_ok {
+            flags |= FLAG_TELEGRAM_CRC_OK;
+            verdicts.push(String::from("telegram CRC ok"));
+        } else {
+            verdicts.push(String::from("telegram CRC error"));
+        }
+        match slave_ack {
+            Some(ack) => {
+                flags |= FLAG_SLAVE_ACK;
+                data.push(*ack);
+                verdicts.push(ack_verdict("", *ack));
+            }
+            None if !Address(telegram.dest).is_broadcast() => verdicts.push("no ACK".into()),
+            None => {}
+        }
+        if let Some(reply) = reply {
+            flags |= FLAG_REPLY;
+            data.push(reply.as_bytes().len() as u8);
+            data.extend_from_slice(reply.as_bytes());
+            if *reply_crc_ok == Some(true) {
+                flags |= FLAG_REPLY_CRC_OK;
+                verdicts.push("reply CRC ok".into());
+            } else {
+                verdicts.push("reply CRC error".into());
+            }
+        }
+        if let Some(ack) = master_ack {
+            flags |= FLAG_MASTER_ACK;
+            data.push(*ack);
+            verdicts.push(ack_verdict("master ", *ack));
+        }
+        data.insert(0, flags);
+
+        self.packet(TELEGRAM_INTERFACE, time, &data, None, &verdicts.join(", "))
+    }
+
+    /// Write the telegram of a result, if it has one.
+    ///
+    /// While sniffing, complete transactions are reported; otherwise only the telegrams
+    /// (`Observed`, `Broadcast`, `Request` and `MasterMessage`) without ACKs and replies.
+    /// Returns whether something was written.
+    pub fn write_result(&mut self, time: SystemTime, res: &ProcessResult) -> io::Result<bool> {
+        let (telegram, kind) = match res {
+            ProcessResult::Transaction { transaction } => {
+                self.write_transaction(time, transaction)?;
+                return Ok(true);
+            }
+            ProcessResult::Observed { telegram } => (telegram, "observed"),
+            ProcessResult::Broadcast { telegram } => (telegram, "broadcast"),
+            ProcessResult::Request { telegram, .. } => (telegram, "request to us"),
+            ProcessResult::MasterMessage { telegram } => (telegram, "master message to us"),
+            _ => return Ok(false),
+        };
+
+        let mut data = vec![FLAG_TELEGRAM_CRC_OK];
+        push_telegram(&mut data, telegram);
+        self.packet(
+            TELEGRAM_INTERFACE,
+            time,
+            &data,
+            None,
+            &format!("{kind}, telegram CRC ok"),
+        )?;
+
+        Ok(true)
+    }
+
+    /// Write symbols as they were on the bus, with escapes and CRCs
+    pub fn write_symbols(
+        &mut self,
+        time: SystemTime,
+        symbols: &[u8],
+        direction: Direction,
+    ) -> io::Result<()> {
+        self.packet(SYMBOL_INTERFACE, time, symbols, Some(direction), "")
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    fn packet(
+        &mut self,
+        interface: u32,
+        time: SystemTime,
+        data: &[u8],
+        direction: Option<Direction>,
+        comment: &str,
+    ) -> io::Result<()> {
+        // microseconds, the default resolution
+        let micros = time
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as u64;
+
+        let mut body = Vec::new();
+        body.extend(interface.to_le_bytes());
+        body.extend(((micros >> 32) as u32).to_le_bytes());
+        body.extend((micros as u32).to_le_bytes());
+        body.extend((data.len() as u32).to_le_bytes());
+        body.extend((data.len() as u32).to_le_bytes());
+        body.extend_from_slice(data);
+        pad(&mut body);
+        if let Some(direction) = direction {
+            let flags: u32 = match direction {
+                Direction::Inbound => 1,
+                Direction::Outbound => 2,
+            };
+            push_option(&mut body, OPT_EPB_FLAGS, &flags.to_le_bytes());
+        }
+        if !comment.is_empty() {
+            push_option(&mut body, OPT_COMMENT, comment.as_bytes());
+        }
+        push_option(&mut body, OPT_END, b"");
+
+        self.block(ENHANCED_PACKET, &body)
+    }
+
+    fn block(&mut self, kind: u32, body: &[u8]) -> io::Result<()> {
+        // type and length before and after the body
+        let len = (body.len() + 12) as u32;
+        self.inner.write_all(&kind.to_le_bytes())?;
+        self.inner.write_all(&len.to_le_bytes())?;
+        self.inner.write_all(body)?;
+        self.inner.write_all(&len.to_le_bytes())
+    }
+}
+
+fn ack_verdict(prefix: &str, ack: u8) -> String {
+    match ack {
+        ACK_OK => format!("{prefix}ACK"),
+        ACK_ERR => format!("{prefix}NACK"),
+        other => format!("invalid {prefix}ACK 0x{other:02X}"),
+    }
+}
+
+fn push_telegram(data: &mut Vec<u8>, telegram: &Telegram) {
+    let [pb, sb] = telegram.service.to_be_bytes();
+    data.extend([telegram.src, telegram.dest, pb, sb]);
+    data.push(telegram.data.as_bytes().len() as u8);
+    data.extend_from_slice(telegram.data.as_bytes());
+}
+
+fn push_option(body: &mut Vec<u8>, code: u16, value: &[u8]) {
+    body.extend(code.to_le_bytes());
+    body.extend((value.len() as u16).to_le_bytes());
+    body.extend_from_slice(value);
+    pad(body);
+}
+
+/// Blocks and options are aligned to 32 bits
+fn pad(body: &mut Vec<u8>) {
+    body.resize(body.len().next_multiple_of(4), 0);
+}
@@ -0,0 +1,290 @@
+//! Fixed-capacity queue of outgoing telegrams.
+
+use core::{task::Poll, time::Duration};
+
+use crate::{
+    Address, Clock, EbusDriver, MasterTelegram, Outcome, ProcessResult, SendError, Transmit, SYN,
+};
+
+/// How often a telegram is sent again after a failure
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RetryPolicy {
+    /// Retries after the recipient did not answer before AUTO-SYN
+    pub timeout: u8,
+    /// Retries after the reply CRC was wrong twice
+    pub reply_crc_error: u8,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TxError {
+    /// Sending failed and no retries were left
+    Send(SendError),
+    /// Deadline passed before the telegram could be sent
+    Expired,
+    /// The result has already been taken
+    Gone,
+}
+
+/// Refers to a telegram in a [`TxQueue`], poll it with [`TxQueue::poll`]
+#[derive(Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TxHandle {
+    slot: usize,
+    generation: u32,
+}
+
+struct Entry {
+    msg: MasterTelegram,
+    deadline: Option<Duration>,
+    retry: RetryPolicy,
+    seq: u32,
+    /// Nobody is interested in the result, free the slot when done
+    detached: bool,
+}
+
+enum SlotState {
+    Free,
+    Queued(Entry),
+    Done(Result<Outcome, TxError>),
+}
+
+struct Slot {
+    generation: u32,
+    state: SlotState,
+}
+
+/// Queue of up to `N` telegrams, sent by priority class of the source address, then in
+/// order of enqueueing.
+///
+/// A slot stays occupied until its result has been taken with [`TxQueue::poll`] or the
+/// telegram has been cancelled.
+/// Deadlines are compared with the [`Clock`] passed to [`TxQueue::process`].
+pub struct TxQueue<const N: usize> {
+    slots: [Slot; N],
+    /// Sequence number of the next telegram
+    seq: u32,
+    /// Slot of the telegram the driver is sending
+    current: Option<usize>,
+}
+
+impl Slot {
+    fn finish(&mut self, res: Result<Outcome, TxError>) {
+        self.state = match &self.state {
+            SlotState::Queued(entry) if entry.detached => {
+                self.generation = self.generation.wrapping_add(1);
+                SlotState::Free
+            }
+            _ => SlotState::Done(res),
+        };
+    }
+}
+
+impl<const N: usize> Default for TxQueue<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> TxQueue<N> {
+    pub const fn new() -> Self {
+        TxQueue {
+            slots: [const {
+                Slot {
+                    generation: 0,
+                    state: SlotState::Free,
+                }
+            }; N],
+            seq: 0,
+            current: None,
+        }
+    }
+
+    /// Queue a telegram, the telegram is given back if the queue is full.
+    ///
+    /// It is dropped (with [`TxError::Expired`]) if it could not be sent before `deadline`.
+    pub fn enqueue(
+        &mut self,
+        msg: MasterTelegram,
+        deadline: Option<Duration>,
+        retry: RetryPolicy,
+    ) -> Result<TxHandle, MasterTelegram> {
+        let Some(slot) = self
+            .slots
+            .iter()
+            .position(|slot| matches!(slot.state, SlotState::Free))
+        else {
+            return Err(msg);
+        };
+
+        let seq = self.seq;
+        self.seq = self.seq.wrapping_add(1);
+        self.slots[slot].state = SlotState::Queued(Entry {
+            msg,
+            deadline,
+            retry,
+            seq,
+            detached: false,
+        });
+
+        Ok(TxHandle {
+            slot,
+            generation: self.slots[slot].generation,
+        })
+    }
+
+    /// Take the final result of a telegram, `Pending` while it is queued
+    pub fn poll(&mut self, handle: &TxHandle) -> Poll<Result<Outcome, TxError>> {
+        let Some(slot) = self.slot_mut(handle) else {
+            return Poll::Ready(Err(TxError::Gone));
+        };
+        if let SlotState::Queued(_) = slot.state {
+            return Poll::Pending;
+        }
+
+        match core::mem::replace(&mut slot.state, SlotState::Free) {
+            SlotState::Done(res) => {
+                slot.generation = slot.generation.wrapping_add(1);
+                Poll::Ready(res)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Remove a telegram (or its result), returns `false` if it is being sent right now
+    pub fn cancel(&mut self, handle: TxHandle) -> bool {
+        if self.current == Some(handle.slot) {
+            return false;
+        }
+        if let Some(slot) = self.slot_mut(&handle) {
+            slot.state = SlotState::Free;
+            slot.generation = slot.generation.wrapping_add(1);
+        }
+
+        true
+    }
+
+    /// Drop the result of a telegram once it is done, freeing its slot
+    pub fn detach(&mut self, handle: TxHandle) {
+        let Some(slot) = self.slot_mut(&handle) else {
+            return;
+        };
+        match &mut slot.state {
+            SlotState::Queued(entry) => entry.detached = true,
+            _ => {
+                slot.state = SlotState::Free;
+                slot.generation = slot.generation.wrapping_add(1);
+            }
+        }
+    }
+
+    /// `true` if no telegram is waiting to be sent
+    pub fn is_empty(&self) -> bool {
+        !self
+            .slots
+            .iter()
+            .any(|slot| matches!(slot.state, SlotState::Queued(_)))
+    }
+
+    /// The telegram being sent or to be sent next
+    pub fn next_msg(&self) -> Option<&MasterTelegram> {
+        match &self.slots[self.current?].state {
+            SlotState::Queued(entry) => Some(&entry.msg),
+            _ => None,
+        }
+    }
+
+    /// Process a byte with the next telegram of the queue and record its result.
+    ///
+    /// The result is passed on, `VetReply` still has to be handled by the caller.
+    pub fn process<T: Transmit>(
+        &mut self,
+        driver: &mut EbusDriver,
+        word: u8,
+        transmit: &mut T,
+        clock: &impl Clock,
+        is_low_latency: bool,
+    ) -> Result<ProcessResult, T::Error> {
+        let sending = driver.is_sending();
+        if !sending {
+            self.expire(clock.now());
+            self.current = self.select();
+        }
+
+        // a SYN ends our transaction, the next telegram is only sent after the following SYN
+        // so the result of this one is known before
+        let msg = if word == SYN && sending {
+            None
+        } else {
+            self.next_msg()
+        };
+        let res = driver.process(word, transmit, clock, msg, is_low_latency)?;
+        self.handle(&res);
+
+        Ok(res)
+    }
+
+    fn handle(&mut self, res: &ProcessResult) {
+        let Some(res) = res.outcome() else {
+            return;
+        };
+        let Some(current) = self.current.take() else {
+            return;
+        };
+        let SlotState::Queued(entry) = &mut self.slots[current].state else {
+            return;
+        };
+
+        let retries = match res {
+            Err(SendError::Timeout) => Some(&mut entry.retry.timeout),
+            Err(SendError::ReplyCrcError) => Some(&mut entry.retry.reply_crc_error),
+            _ => None,
+        };
+        if let Some(retries @ 1..) = retries {
+            // stays queued with its original position
+            *retries -= 1;
+            return;
+        }
+
+        self.slots[current].finish(res.map_err(TxError::Send));
+    }
+
+    fn expire(&mut self, now: Duration) {
+        for slot in &mut self.slots {
+            if let SlotState::Queued(entry) = &slot.state {
+                if entry.deadline.is_some_and(|deadline| now > deadline) {
+                    slot.finish(Err(TxError::Expired));
+                }
+            }
+        }
+    }
+
+    /// Slot with the highest priority class, the oldest one among equals
+    fn select(&self) -> Option<usize> {
+        let oldest = self.seq;
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(i, slot)| match &slot.state {
+                SlotState::Queued(entry) => {
+                    let class = Address(entry.msg.telegram.src)
+                        .priority_class()
+                        .unwrap_or(u8::MAX);
+                    // age instead of sequence number to be correct on wrap-around
+                    Some((class, core::cmp::Reverse(oldest.wrapping_sub(entry.seq)), i))
+                }
+                _ => None,
+            })
+            .min()
+            .map(|(_, _, i)| i)
+    }
+
+    fn slot_mut(&mut self, handle: &TxHandle) -> Option<&mut Slot> {
+        self.slots
+            .get_mut(handle.slot)
+            .filter(|slot| slot.generation == handle.generation)
+    }
+}
@@ -0,0 +1,789 @@
+//! Record bus traffic and replay it (feature `std`).
+//!
+//! A recording is a log of the raw bytes received from and transmitted to the bus, each
+//! with the time relative to the previous record. Wrap the transmitter in a [`Recorder`]
+//! and pass received bytes to [`Recorder::receive`] before handing them to the driver;
+//! [`replay`] feeds the received bytes of a recording into a fresh driver.
+//!
+//! The bytes alone only replay the same way for a driver that just listens. To replay a
+//! driver that sends telegrams or answers as slave, also record what it was asked to do
+//! ([`Recorder::offer`], [`Recorder::reply`], [`Recorder::vet_timeout`]), and its
+//! results ([`Recorder::result`]) to have the replay compare them. All of these are
+//! optional.
+//!
+//! The driver has to be configured the same way for the replay (addresses, observing,
+//! ...), arbitration by an adapter is not supported.
+//!
+//! # Format
+//!
+//! The header `EBUSREC` and the version byte `1` are followed by records of
+//!
+//! * time since the previous record in µs, LEB128 encoded
+//! * a tag byte
+//! * the payload of the tag, telegrams as `QQ ZZ PB SB NN DD..`
+//!
+//! | tag    | event                               | payload                           |
+//! |--------|-------------------------------------|-----------------------------------|
+//! | `0x00` | `Rx`                                | length (LEB128), bytes as on the wire |
+//! | `0x01` | `Rx`, last byte `is_low_latency`    | length (LEB128), bytes as on the wire |
+//! | `0x02` | `Tx`                                | length (LEB128), bytes as on the wire |
+//! | `0x03` | `Msg(None)`                         |                                   |
+//! | `0x04` | `Msg(Some(_))`                      | telegram, flags                   |
+//! | `0x05` | `Reply(None)`                       |                                   |
+//! | `0x06` | `Reply(Some(_))`                    | length, data                      |
+//! | `0x07` | `VetTimeout`                        |                                   |
+//! | `0x08` | `Request`                           | telegram                          |
+//! | `0x09` | `Result`                            | variant index, fields             |
+
+use core::{cell::Cell, convert::Infallible, time::Duration};
+use std::{boxed::Box, collections::VecDeque, io, vec::Vec};
+
+use crate::{
+    Buffer, Clock, EbusDriver, MasterTelegram, ProcessResult, Telegram, TelegramFlag,
+    TelegramFlags, Transaction, Transmit, MAX_BUF, SYN,
+};
+
+const HEADER: &[u8; 8] = b"EBUSREC\x01";
+
+/// Most bytes in one `Rx` / `Tx` record, [`Recorder`] splits longer ones
+pub const MAX_RECORD: usize = 256;
+
+const RX: u8 = 0x00;
+const RX_LOW_LATENCY: u8 = 0x01;
+const TX: u8 = 0x02;
+const MSG_NONE: u8 = 0x03;
+const MSG: u8 = 0x04;
+const REPLY_ACK: u8 = 0x05;
+const REPLY: u8 = 0x06;
+const VET_TIMEOUT: u8 = 0x07;
+const REQUEST: u8 = 0x08;
+const RESULT: u8 = 0x09;
+
+/// What happened at a record
+#[derive(Debug, PartialEq)]
+pub enum Event {
+    /// Bytes received from the bus, the last one is passed to the driver with
+    /// `is_low_latency`
+    Rx {
+        bytes: Vec<u8>,
+        is_low_latency: bool,
+    },
+    /// Bytes transmitted by the driver
+    Tx(Vec<u8>),
+    /// Telegram offered to the driver from now on
+    Msg(Option<MasterTelegram>),
+    /// Answer to the last request, [`EbusDriver::reply_as_slave`] or
+    /// [`EbusDriver::reply_ack`] if `None`
+    Reply(Option<Buffer>),
+    /// [`EbusDriver::vet_timeout`]
+    VetTimeout,
+    /// `ProcessResult::Request`, without the token
+    Request(Telegram),
+    /// Any other result than `ProcessResult::None`
+    Result(ProcessResult),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Record {
+    /// Time since the start of the recording
+    pub at: Duration,
+    pub event: Event,
+}
+
+/// Writes records in the recording format
+pub struct Writer<W> {
+    inner: W,
+    last: Duration,
+}
+
+impl<W: io::Write> Writer<W> {
+    /// Write the header
+    pub fn new(mut inner: W) -> io::Result<Self> {
+        inner.write_all(HEADER)?;
+
+        Ok(Writer {
+            inner,
+            last: Duration::ZERO,
+        })
+    }
+
+    /// Write a record, records have to be written in order of time.
+    ///
+    /// Fails for `Rx` / `Tx` with more than [`MAX_RECORD`] bytes, the [`Reader`] would not
+    /// accept them.
+    pub fn write(&mut self, record: &Record) -> io::Result<()> {
+        match &record.event {
+            Event::Rx {
+                bytes,
+                is_low_latency,
+            } => {
+                let tag = if *is_low_latency { RX_LOW_LATENCY } else { RX };
+                self.write_bytes(record.at, tag, bytes)
+            }
+            Event::Tx(bytes) => self.write_bytes(record.at, TX, bytes),
+            Event::Msg(None) => self.write_with(record.at, |buf| buf.push(MSG_NONE)),
+            Event::Msg(Some(msg)) => self.write_with(record.at, |buf| {
+                buf.push(MSG);
+                push_telegram(buf, &msg.telegram);
+                let flags = [TelegramFlag::NeedsDataCrc, TelegramFlag::ExpectReply]
+                    .into_iter()
+                    .filter(|&flag| msg.flags & flag)
+                    .fold(0, |flags, flag| flags | 1 << flag as u8);
+                buf.push(flags);
+            }),
+            Event::Reply(None) => self.write_with(record.at, |buf| buf.push(REPLY_ACK)),
+            Event::Reply(Some(data)) => self.write_with(record.at, |buf| {
+                buf.push(REPLY);
+                push_buffer(buf, data);
+            }),
+            Event::VetTimeout => self.write_with(record.at, |buf| buf.push(VET_TIMEOUT)),
+            Event::Request(telegram) => self.write_with(record.at, |buf| {
+                buf.push(REQUEST);
+                push_telegram(buf, telegram);
+            }),
+            Event::Result(res) => self.write_result(record.at, res),
+        }
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    fn write_bytes(&mut self, at: Duration, tag: u8, bytes: &[u8]) -> io::Result<()> {
+        if bytes.len() > MAX_RECORD {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "more than MAX_RECORD bytes",
+            ));
+        }
+
+        self.write_with(at, |buf| {
+            buf.push(tag);
+            push_varint(buf, bytes.len() as u64);
+            buf.extend_from_slice(bytes);
+        })
+    }
+
+    /// `Request` is written without its token
+    fn write_result(&mut self, at: Duration, res: &ProcessResult) -> io::Result<()> {
+        self.write_with(at, |buf| match res {
+            ProcessResult::Request { telegram, .. } => {
+                buf.push(REQUEST);
+                push_telegram(buf, telegram);
+            }
+            res => {
+                buf.push(RESULT);
+                push_result(buf, res);
+            }
+        })
+    }
+
+    fn write_with(&mut self, at: Duration, encode: impl FnOnce(&mut Vec<u8>)) -> io::Result<()> {
+        let delta = at.saturating_sub(self.last);
+        self.last = at;
+
+        let mut buf = Vec::new();
+        push_varint(&mut buf, delta.as_micros() as u64);
+        encode(&mut buf);
+
+        self.inner.write_all(&buf)
+    }
+}
+
+/// Reads the records of a recording
+pub struct Reader<R> {
+    inner: R,
+    at: Duration,
+}
+
+impl<R: io::Read> Reader<R> {
+    /// Read and check the header
+    pub fn new(mut inner: R) -> io::Result<Self> {
+        let mut header = [0; HEADER.len()];
+        inner.read_exact(&mut header)?;
+        if &header != HEADER {
+            return Err(invalid("not a recording or unsupported version"));
+        }
+
+        Ok(Reader {
+            inner,
+            at: Duration::ZERO,
+        })
+    }
+
+    /// Next record, `None` at the end of the recording
+    pub fn read(&mut self) -> io::Result<Option<Record>> {
+        let mut first = [0];
+        if self.inner.read(&mut first)? == 0 {
+            return Ok(None);
+        }
+        let delta = self.varint_from(first[0])?;
+        self.at += Duration::from_micros(delta);
+
+        let event = match self.byte()? {
+            tag @ (RX | RX_LOW_LATENCY) => Event::Rx {
+                bytes: self.bytes()?,
+                is_low_latency: tag == RX_LOW_LATENCY,
+            },
+            TX => Event::Tx(self.bytes()?),
+            MSG_NONE => Event::Msg(None),
+            MSG => {
+                let telegram = self.telegram()?;
+                let flags = self.byte()?;
+                let flags = [TelegramFlag::NeedsDataCrc, TelegramFlag::ExpectReply]
+                    .into_iter()
+                    .filter(|&flag| flags & 1 << flag as u8 != 0)
+                    .fold(TelegramFlags::none(), |flags, flag| flag | flags);
+                Event::Msg(Some(MasterTelegram { telegram, flags }))
+            }
+            REPLY_ACK => Event::Reply(None),
+            REPLY => Event::Reply(Some(self.buffer()?)),
+            VET_TIMEOUT => Event::VetTimeout,
+            REQUEST => Event::Request(self.telegram()?),
+            RESULT => Event::Result(self.result()?),
+            _ => return Err(invalid("unknown tag")),
+        };
+
+        Ok(Some(Record { at: self.at, event }))
+    }
+
+    fn byte(&mut self) -> io::Result<u8> {
+        let mut byte = [0];
+        self.inner.read_exact(&mut byte)?;
+
+        Ok(byte[0])
+    }
+
+    fn varint_from(&mut self, mut byte: u8) -> io::Result<u64> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            value |= ((byte & 0x7F) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+            byte = self.byte()?;
+        }
+
+        Err(invalid("varint too long"))
+    }
+
+    fn bytes(&mut self) -> io::Result<Vec<u8>> {
+        let first = self.byte()?;
+        let len = self.varint_from(first)?;
+        if len > MAX_RECORD as u64 {
+            return Err(invalid("record too long"));
+        }
+        let mut bytes = std::vec![0; len as usize];
+        self.inner.read_exact(&mut bytes)?;
+
+        Ok(bytes)
+    }
+
+    fn buffer(&mut self) -> io::Result<Buffer> {
+        let len = self.byte()? as usize;
+        if len > MAX_BUF {
+            return Err(invalid("more than MAX_BUF bytes"));
+        }
+        let mut data = [0; MAX_BUF];
+        self.inner.read_exact(&mut data[..len])?;
+
+        Ok(Buffer::from_parts(data, len as u8))
+    }
+
+    fn telegram(&mut self) -> io::Result<Telegram> {
+        Ok(Telegram {
+            src: self.byte()?,
+            dest: self.byte()?,
+            service: u16::from_be_bytes([self.byte()?, self.byte()?]),
+            data: self.buffer()?,
+        })
+    }
+
+    fn transaction(&mut self) -> io::Result<Transaction> {
+        let telegram = self.telegram()?;
+        let bits = self.byte()?;
+        let mut opt = |bit: u8| -> io::Result<Option<u8>> {
+            if bits & bit == 0 {
+                return Ok(None);
+            }
+            self.byte().map(Some)
+        };
+        let slave_ack = opt(SLAVE_ACK)?;
+        let master_ack = opt(MASTER_ACK)?;
+        let reply = if bits & HAS_REPLY != 0 {
+            Some(self.buffer()?)
+        } else {
+            None
+        };
+
+        Ok(Transaction {
+            telegram,
+            telegram_crc_ok: bits & TELEGRAM_CRC_OK != 0,
+            slave_ack,
+            reply,
+            reply_crc_ok: (bits & REPLY_CRC != 0).then_some(bits & REPLY_CRC_OK != 0),
+            master_ack,
+        })
+    }
+
+    /// Any result but `Request`, which has its own tag
+    fn result(&mut self) -> io::Result<ProcessResult> {
+        Ok(match self.byte()? {
+            0 => ProcessResult::None,
+            1 => ProcessResult::VetReply {
+                timeout_ms: u16::from_be_bytes([self.byte()?, self.byte()?]),
+            },
+            2 => ProcessResult::SlaveAckOk {
+                attempts: self.byte()?,
+            },
+            3 => ProcessResult::SlaveAckErr {
+                attempts: self.byte()?,
+            },
+            4 => ProcessResult::MasterAckOk {
+                attempts: self.byte()?,
+            },
+            5 => ProcessResult::MasterAckErr {
+                attempts: self.byte()?,
+            },
+            6 => ProcessResult::BroadcastSent,
+            7 => ProcessResult::Timeout,
+            8 => ProcessResult::InvalidSource,
+            9 => ProcessResult::TelegramCrcError,
+            10 => ProcessResult::ReplyCrcError,
+            11 => ProcessResult::ReplyLenOverflow { len: self.byte()? },
+            12 => ProcessResult::MasterMessage {
+                telegram: self.telegram()?,
+            },
+            13 => ProcessResult::Observed {
+                telegram: self.telegram()?,
+            },
+            14 => ProcessResult::Transaction {
+                transaction: self.transaction()?,
+            },
+            15 => ProcessResult::Broadcast {
+                telegram: self.telegram()?,
+            },
+            16 => ProcessResult::Reply {
+                data: self.buffer()?,
+                clean: self.byte()? != 0,
+                attempts: self.byte()?,
+            },
+            _ => return Err(invalid("unknown result")),
+        })
+    }
+}
+
+impl<R: io::Read> Iterator for Reader<R> {
+    type Item = io::Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read().transpose()
+    }
+}
+
+// bits of a transaction
+const TELEGRAM_CRC_OK: u8 = 1 << 0;
+const SLAVE_ACK: u8 = 1 << 1;
+const MASTER_ACK: u8 = 1 << 2;
+const HAS_REPLY: u8 = 1 << 3;
+const REPLY_CRC: u8 = 1 << 4;
+const REPLY_CRC_OK: u8 = 1 << 5;
+
+fn push_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn push_buffer(buf: &mut Vec<u8>, data: &Buffer) {
+    buf.push(data.as_bytes().len() as u8);
+    buf.extend_from_slice(data.as_bytes());
+}
+
+fn push_telegram(buf: &mut Vec<u8>, telegram: &Telegram) {
+    buf.extend([telegram.src, telegram.dest]);
+    buf.extend(telegram.service.to_be_bytes());
+    push_buffer(buf, &telegram.data);
+}
+
+fn push_result(buf: &mut Vec<u8>, res: &ProcessResult) {
+    match res {
+        ProcessResult::None => buf.push(0),
+        ProcessResult::VetReply { timeout_ms } => {
+            buf.push(1);
+            buf.extend(timeout_ms.to_be_bytes());
+        }
+        ProcessResult::SlaveAckOk { attempts } => buf.extend([2, *attempts]),
+        ProcessResult::SlaveAckErr { attempts } => buf.extend([3, *attempts]),
+        ProcessResult::MasterAckOk { attempts } => buf.extend([4, *attempts]),
+        ProcessResult::MasterAckErr { attempts } => buf.extend([5, *attempts]),
+        ProcessResult::BroadcastSent => buf.push(6),
+        ProcessResult::Timeout => buf.push(7),
+        ProcessResult::InvalidSource => buf.push(8),
+        ProcessResult::TelegramCrcError => buf.push(9),
+        ProcessResult::ReplyCrcError => buf.push(10),
+        ProcessResult::ReplyLenOverflow { len } => buf.extend([11, *len]),
+        ProcessResult::MasterMessage { telegram } => {
+            buf.push(12);
+            push_telegram(buf, telegram);
+        }
+        ProcessResult::Observed { telegram } => {
+            buf.push(13);
+            push_telegram(buf, telegram);
+        }
+        ProcessResult::Transaction { transaction } => {
+            buf.push(14);
+            push_telegram(buf, &transaction.telegram);
+            let bits = [
+                (TELEGRAM_CRC_OK, transaction.telegram_crc_ok),
+                (SLAVE_ACK, transaction.slave_ack.is_some()),
+                (MASTER_ACK, transaction.master_ack.is_some()),
+                (HAS_REPLY, transaction.reply.is_some()),
+                (REPLY_CRC, transaction.reply_crc_ok.is_some()),
+                (REPLY_CRC_OK, transaction.reply_crc_ok == Some(true)),
+            ]
+            .into_iter()
+            .filter(|(_, set)| *set)
+            .fold(0, |bits, (bit, _)| bits | bit);
+            buf.push(bits);
+            buf.extend(transaction.slave_ack);
+            buf.extend(transaction.master_ack);
+            if let Some(reply) = &transaction.reply {
+                push_buffer(buf, reply);
+            }
+        }
+        ProcessResult::Broadcast { telegram } => {
+            buf.push(15);
+            push_telegram(buf, telegram);
+        }
+        ProcessResult::Reply {
+            data,
+            clean,
+            attempts,
+        } => {
+            buf.push(16);
+            push_buffer(buf, data);
+            buf.extend([*clean as u8, *attempts]);
+        }
+        ProcessResult::Request { .. } => unreachable!("written with its own tag"),
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[derive(Debug)]
+pub enum Error<E> {
+    Transmit(E),
+    /// Writing the recording failed
+    Io(io::Error),
+}
+
+/// [`Transmit`] wrapper recording everything transmitted through `T`, and the received
+/// bytes passed to [`Recorder::receive`].
+///
+/// Records are timestamped with `clock`, usually a reference to the clock of the driver.
+pub struct Recorder<T, C, W> {
+    transmit: T,
+    clock: C,
+    writer: Writer<W>,
+    /// Clock time of the first record
+    start: Option<Duration>,
+    /// Telegram recorded by the last `offer`
+    msg: Option<MasterTelegram>,
+}
+
+impl<T: Transmit, C: Clock, W: io::Write> Recorder<T, C, W> {
+    /// Start a recording written to `writer`
+    pub fn new(transmit: T, clock: C, writer: W) -> io::Result<Self> {
+        Ok(Recorder {
+            transmit,
+            clock,
+            writer: Writer::new(writer)?,
+            start: None,
+            msg: None,
+        })
+    }
+
+    /// Record bytes received from the bus, as they were read.
+    ///
+    /// The replay passes the last of them to the driver with `is_low_latency`. More than
+    /// [`MAX_RECORD`] bytes are split into several records.
+    pub fn receive(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let mut chunks = bytes.chunks(MAX_RECORD).peekable();
+        while let Some(chunk) = chunks.next() {
+            self.write(Event::Rx {
+                bytes: chunk.to_vec(),
+                is_low_latency: chunks.peek().is_none(),
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Record the telegram passed to the driver as `next_msg`, call it before every
+    /// `process`. Only changes are written.
+    pub fn offer(&mut self, next_msg: Option<&MasterTelegram>) -> io::Result<()> {
+        if next_msg == self.msg.as_ref() {
+            return Ok(());
+        }
+        self.msg = next_msg.cloned();
+
+        self.write(Event::Msg(self.msg.clone()))
+    }
+
+    /// Record the answer to a request before passing it to [`EbusDriver::reply_as_slave`],
+    /// `None` for [`EbusDriver::reply_ack`]
+    pub fn reply(&mut self, data: Option<&[u8]>) -> io::Result<()> {
+        let data = data.map(|data| Buffer::from_slice(&data[..data.len().min(MAX_BUF)]));
+
+        self.write(Event::Reply(data))
+    }
+
+    /// Record a call of [`EbusDriver::vet_timeout`], before making it
+    pub fn vet_timeout(&mut self) -> io::Result<()> {
+        self.write(Event::VetTimeout)
+    }
+
+    /// Record a result of the driver for the replay to compare, `None` is skipped
+    pub fn result(&mut self, res: &ProcessResult) -> io::Result<()> {
+        if res.is_none() {
+            return Ok(());
+        }
+        let at = self.now();
+
+        self.writer.write_result(at, res)
+    }
+
+    pub fn transmit(&mut self) -> &mut T {
+        &mut self.transmit
+    }
+
+    pub fn into_inner(self) -> (T, W) {
+        (self.transmit, self.writer.into_inner())
+    }
+
+    fn now(&mut self) -> Duration {
+        let now = self.clock.now();
+        let start = *self.start.get_or_insert(now);
+
+        now.saturating_sub(start)
+    }
+
+    fn write(&mut self, event: Event) -> io::Result<()> {
+        let at = self.now();
+
+        self.writer.write(&Record { at, event })
+    }
+
+    fn write_tx(&mut self, bytes: &[u8]) -> io::Result<()> {
+        for chunk in bytes.chunks(MAX_RECORD) {
+            self.write(Event::Tx(chunk.to_vec()))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<T: Transmit, C: Clock, W: io::Write> Transmit for Recorder<T, C, W> {
+    type Error = Error<T::Error>;
+
+    fn transmit_raw(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.write_tx(bytes).map_err(Error::Io)?;
+        self.transmit.transmit_raw(bytes).map_err(Error::Transmit)
+    }
+
+    fn clear_buffer(&mut self) -> Result<(), Self::Error> {
+        self.transmit.clear_buffer().map_err(Error::Transmit)
+    }
+
+    fn transmit_syn(&mut self) -> Result<(), Self::Error> {
+        self.write_tx(&[SYN]).map_err(Error::Io)?;
+        self.transmit.transmit_syn().map_err(Error::Transmit)
+    }
+
+    fn transmit_start(&mut self, src: u8) -> Result<(), Self::Error> {
+        self.write_tx(&[src]).map_err(Error::Io)?;
+        self.transmit.transmit_start(src).map_err(Error::Transmit)
+    }
+}
+
+/// Outcome of a [`replay`]
+#[derive(Debug, Default)]
+pub struct Replay {
+    /// Results other than `ProcessResult::None` as `Event::Result` / `Event::Request`,
+    /// in order
+    pub results: Vec<Event>,
+    /// Everything the driver transmitted
+    pub transmitted: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    Mismatch(Box<Mismatch>),
+}
+
+impl From<io::Error> for ReplayError {
+    fn from(e: io::Error) -> Self {
+        ReplayError::Io(e)
+    }
+}
+
+/// The driver came up with `found` where the recording has `expected`, `None` if there was
+/// nothing (more)
+#[derive(Debug, PartialEq)]
+pub struct Mismatch {
+    pub at: Duration,
+    pub expected: Option<Event>,
+    pub found: Option<Event>,
+}
+
+/// Feed a recording into `driver`, at the time the records were written.
+///
+/// Received bytes are processed with the recorded telegram offered, replies and vetting
+/// timeouts are passed on. Fails on the first `Tx` or result record the driver does not
+/// come up with in the same order; the bytes it transmits, or its results, are only
+/// checked if the recording has any.
+pub fn replay<R: io::Read>(
+    driver: &mut EbusDriver,
+    reader: Reader<R>,
+) -> Result<Replay, ReplayError> {
+    let clock = ReplayClock(Cell::new(Duration::ZERO));
+    let mut transmit = Sink::default();
+    let mut replay = Replay::default();
+    let mut msg = None;
+    // of the last request, for the recorded reply
+    let mut token = None;
+    // results not compared yet
+    let mut results = VecDeque::new();
+    let (mut has_tx, mut has_results) = (false, false);
+
+    for record in reader {
+        let Record { at, event } = record?;
+        let mismatch = |expected, found| {
+            ReplayError::Mismatch(Box::new(Mismatch {
+                at,
+                expected,
+                found,
+            }))
+        };
+        clock.0.set(at);
+
+        let Ok(()) = match event {
+            Event::Rx {
+                bytes,
+                is_low_latency,
+            } => {
+                let last = bytes.len().saturating_sub(1);
+                for (i, word) in bytes.into_iter().enumerate() {
+                    let is_low_latency = is_low_latency && i == last;
+                    let Ok(res) =
+                        driver.process(word, &mut transmit, &clock, msg.as_ref(), is_low_latency);
+                    let event = match res {
+                        ProcessResult::None => continue,
+                        ProcessResult::Request { telegram, token: t } => {
+                            token = Some(t);
+                            Event::Request(telegram)
+                        }
+                        res => Event::Result(res),
+                    };
+                    results.push_back(event);
+                }
+                Ok(())
+            }
+            Event::Tx(bytes) => {
+                has_tx = true;
+                let len = bytes.len().min(transmit.pending.len());
+                let found: Vec<_> = transmit.pending.drain(..len).collect();
+                if found != bytes {
+                    return Err(mismatch(Some(Event::Tx(bytes)), Some(Event::Tx(found))));
+                }
+                continue;
+            }
+            Event::Msg(next_msg) => {
+                msg = next_msg;
+                continue;
+            }
+            Event::Reply(data) => {
+                let Some(token) = token.take() else {
+                    return Err(mismatch(Some(Event::Reply(data)), None));
+                };
+                match data {
+                    Some(data) => driver.reply_as_slave(data.as_bytes(), &mut transmit, token),
+                    None => driver.reply_ack(&mut transmit, token),
+                }
+            }
+            Event::VetTimeout => driver.vet_timeout(&mut transmit),
+            expected @ (Event::Request(_) | Event::Result(_)) => {
+                has_results = true;
+                match results.pop_front() {
+                    Some(found) if found == expected => replay.results.push(found),
+                    found => return Err(mismatch(Some(expected), found)),
+                }
+                continue;
+            }
+        };
+    }
+
+    let at = clock.0.get();
+    let found = if has_tx && !transmit.pending.is_empty() {
+        Some(Event::Tx(transmit.pending.drain(..).collect()))
+    } else if has_results {
+        results.pop_front()
+    } else {
+        None
+    };
+    if found.is_some() {
+        return Err(ReplayError::Mismatch(Box::new(Mismatch {
+            at,
+            expected: None,
+            found,
+        })));
+    }
+    replay.results.extend(results);
+    replay.transmitted = transmit.sent;
+
+    Ok(replay)
+}
+
+/// Time of the record being replayed
+struct ReplayClock(Cell<Duration>);
+
+impl Clock for ReplayClock {
+    fn now(&self) -> Duration {
+        self.0.get()
+    }
+
+    fn delay(&self, _duration: Duration) {}
+}
+
+/// Collects what the driver transmits during a replay
+#[derive(Default)]
+struct Sink {
+    sent: Vec<u8>,
+    /// Not compared with the recording yet
+    pending: VecDeque<u8>,
+}
+
+impl Transmit for Sink {
+    type Error = Infallible;
+
+    fn transmit_raw(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.sent.extend_from_slice(bytes);
+        self.pending.extend(bytes);
+
+        Ok(())
+    }
+
+    fn clear_buffer(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
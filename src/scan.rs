@@ -0,0 +1,115 @@
+//! Discover the participants of a bus by sending identification requests.
+
+use crate::{
+    service::Identification, Address, Clock, EbusDriver, MasterTelegram, Outcome, ProcessResult,
+    SendError, Transmit,
+};
+
+/// Sends an identification request (0x0704) to every slave address, one after another.
+///
+/// Masters are found through their slave address (master + 5).
+/// Either drive the [`EbusDriver`] through [`Scanner::process`], or pass
+/// [`Scanner::next_msg`] to it and every result to [`Scanner::handle`]. When sending
+/// some other way, e.g. through a [`crate::host::Handle`], pass the outcome to
+/// [`Scanner::handle_outcome`].
+pub struct Scanner {
+    src: u8,
+    /// The slave address currently being scanned, `None` when done
+    target: Option<u8>,
+    msg: Option<MasterTelegram>,
+    devices: [Option<Identification>; 256],
+}
+
+impl Scanner {
+    /// Create a scanner sending with master address `src`
+    pub fn new(src: u8) -> Self {
+        let mut scanner = Scanner {
+            src,
+            target: None,
+            msg: None,
+            devices: [const { None }; 256],
+        };
+        scanner.advance(0);
+
+        scanner
+    }
+
+    /// The telegram to send next
+    pub fn next_msg(&self) -> Option<&MasterTelegram> {
+        self.msg.as_ref()
+    }
+
+    /// The slave address currently being scanned
+    pub fn target(&self) -> Option<u8> {
+        self.target
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.target.is_none()
+    }
+
+    /// Process a byte with the current telegram and handle the result.
+    ///
+    /// The result is passed on, `VetReply` still has to be handled by the caller.
+    pub fn process<T: Transmit>(
+        &mut self,
+        driver: &mut EbusDriver,
+        word: u8,
+        transmit: &mut T,
+        clock: &impl Clock,
+        is_low_latency: bool,
+    ) -> Result<ProcessResult, T::Error> {
+        let res = driver.process(word, transmit, clock, self.msg.as_ref(), is_low_latency)?;
+        self.handle(&res);
+
+        Ok(res)
+    }
+
+    /// Handle a result of `EbusDriver::process`, moves on to the next address when
+    /// the current request is finished
+    pub fn handle(&mut self, res: &ProcessResult) {
+        if let Some(outcome) = res.outcome() {
+            self.handle_outcome(outcome);
+        }
+    }
+
+    /// Handle the outcome of the current request and move on to the next address
+    pub fn handle_outcome(&mut self, outcome: Result<Outcome, SendError>) {
+        let Some(target) = self.target else {
+            return;
+        };
+
+        match outcome {
+            Ok(Outcome::Reply { data, .. }) => match Identification::parse(data.as_bytes()) {
+                Ok(id) => self.devices[target as usize] = Some(id),
+                Err(_e) => {
+                    warn!("scan: invalid identification from 0x{:X}: {:?}", target, _e);
+                }
+            },
+            _ => {
+                // nobody (sane) there
+            }
+        }
+
+        self.advance(target as u16 + 1);
+    }
+
+    /// Devices found so far, by slave address
+    pub fn devices(&self) -> impl Iterator<Item = (Address, &Identification)> {
+        self.devices
+            .iter()
+            .enumerate()
+            .filter_map(|(addr, id)| Some((Address(addr as u8), id.as_ref()?)))
+    }
+
+    fn advance(&mut self, from: u16) {
+        let own = Address(self.src).slave_of_master();
+        self.target = (from..=0xFF)
+            .map(|addr| Address(addr as u8))
+            .find(|&addr| addr.is_slave() && Some(addr) != own)
+            .map(u8::from);
+        self.msg = self
+            .target
+            .map(|target| Identification::request(self.src, target));
+    }
+}
@@ -0,0 +1,127 @@
+//! Standard services of the eBUS application layer.
+
+use crate::{
+    codec::{Bcd, CodecError},
+    Buffer, MasterTelegram, Telegram, TelegramFlag, TelegramFlags,
+};
+
+/// Identification (PB 0x07, SB 0x04)
+pub const IDENTIFICATION: u16 = 0x0704;
+
+/// Answer to an identification request
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Identification {
+    pub manufacturer: u8,
+    /// Device id, five ASCII characters
+    pub device_id: [u8; 5],
+    pub sw_version: Version,
+    pub hw_version: Version,
+}
+
+/// Version as two BCD encoded numbers
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Version {
+    pub major: u8,
+    pub minor: u8,
+}
+
+impl Identification {
+    /// Length of the answer
+    pub const LEN: usize = 10;
+
+    /// Build the identification request for slave `dest`
+    pub fn request(src: u8, dest: u8) -> MasterTelegram {
+        MasterTelegram {
+            telegram: Telegram {
+                src,
+                dest,
+                service: IDENTIFICATION,
+                data: Buffer::from_slice(&[]),
+            },
+            flags: TelegramFlag::ExpectReply | TelegramFlags::none(),
+        }
+    }
+
+    /// Parse the answer of a slave
+    pub fn parse(data: &[u8]) -> Result<Self, CodecError> {
+        if data.len() < Self::LEN {
+            return Err(CodecError::OutOfBounds);
+        }
+        let buf = Buffer::from_slice(&data[..Self::LEN]);
+
+        Ok(Identification {
+            manufacturer: data[0],
+            device_id: data[1..6].try_into().unwrap(),
+            sw_version: Version::decode(&buf, 6)?,
+            hw_version: Version::decode(&buf, 8)?,
+        })
+    }
+
+    /// Encode the answer, to be passed to `EbusDriver::reply_as_slave`
+    pub fn to_buffer(&self) -> Result<Buffer, CodecError> {
+        let mut buf = Buffer::from_slice(&[0; Self::LEN]);
+        let bytes = buf.as_bytes_mut();
+        bytes[0] = self.manufacturer;
+        bytes[1..6].copy_from_slice(&self.device_id);
+        self.sw_version.encode(&mut buf, 6)?;
+        self.hw_version.encode(&mut buf, 8)?;
+
+        Ok(buf)
+    }
+
+    /// Device id as string, `None` if it is not ASCII
+    pub fn device_id_str(&self) -> Option<&str> {
+        if !self.device_id.is_ascii() {
+            return None;
+        }
+
+        core::str::from_utf8(&self.device_id)
+            .ok()
+            .map(|id| id.trim_end_matches(['\0', ' ']))
+    }
+}
+
+impl Version {
+    fn decode(buf: &Buffer, offset: usize) -> Result<Self, CodecError> {
+        Ok(Version {
+            major: buf.get::<Bcd>(offset)?.ok_or(CodecError::Invalid)?,
+            minor: buf.get::<Bcd>(offset + 1)?.ok_or(CodecError::Invalid)?,
+        })
+    }
+
+    fn encode(&self, buf: &mut Buffer, offset: usize) -> Result<(), CodecError> {
+        buf.set::<Bcd>(offset, Some(self.major))?;
+        buf.set::<Bcd>(offset + 1, Some(self.minor))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::service::{Identification, Version};
+
+    #[test]
+    fn test_round_trip() {
+        // Vaillant (0xB5) "BAI00", SW 01.02, HW 63.01
+        let data = [0xB5, b'B', b'A', b'I', b'0', b'0', 0x01, 0x02, 0x63, 0x01];
+        let id = Identification::parse(&data).unwrap();
+        assert_eq!(id.manufacturer, 0xB5);
+        assert_eq!(id.device_id_str(), Some("BAI00"));
+        assert_eq!(
+            id.hw_version,
+            Version {
+                major: 63,
+                minor: 1
+            }
+        );
+        assert_eq!(id.to_buffer().unwrap().as_bytes(), &data);
+    }
+
+    #[test]
+    fn test_too_short() {
+        assert!(Identification::parse(&[0xB5, b'B']).is_err());
+    }
+}
@@ -0,0 +1,322 @@
+//! Simulated bus with several participants (feature `sim`).
+//!
+//! Time advances in slots of one byte (about 4.2 ms at 2400 baud). In every slot, each
+//! participant may put one byte on the bus; bytes sent at the same time are combined
+//! with wired-AND (0 bits are dominant), like on the real bus. After some idle slots, the
+//! AUTO-SYN generator of the bus sends a SYN; disable it with [`SimBus::set_auto_syn`] to
+//! test drivers acting as generator (`EbusDriver::set_auto_syn`).
+//!
+//! ```
+//! use ebus::{sim::SimBus, EbusDriver, MasterTelegram, Telegram, TelegramFlags, Buffer};
+//! use core::time::Duration;
+//!
+//! let mut bus = SimBus::new();
+//! let mut driver = EbusDriver::new(Duration::ZERO, 0x9B, 0x5C, 0);
+//! driver.set_master_address(0x10).unwrap();
+//! let node = bus.add_node(driver);
+//! bus.add_slave(0x15, |_| Some(vec![0x42]));
+//!
+//! let msg = MasterTelegram {
+//!     telegram: Telegram { src: 0x10, dest: 0x15, service: 0x0700, data: Buffer::from_slice(&[]) },
+//!     flags: ebus::TelegramFlag::ExpectReply | TelegramFlags::none(),
+//! };
+//! bus.queue(node).enqueue(msg, None, Default::default()).unwrap();
+//! bus.run_until(1000, |bus| bus.queue(node).is_empty());
+//! assert!(bus.results(node).iter().any(|r| r.as_reply() == Some(&[0x42])));
+//! ```
+
+use core::time::Duration;
+use std::{boxed::Box, collections::VecDeque, vec::Vec};
+
+use crate::{
+    queue::TxQueue, Clock, EbusDriver, ProcessResult, RequestToken, Telegram, Transmit, SYN,
+};
+
+/// Capacity of the message queue of every node
+pub const QUEUE_LEN: usize = 16;
+
+/// Duration of one slot, one byte (start bit, 8 data bits, stop bit) at 2400 baud
+pub const SLOT: Duration = Duration::from_micros(4167);
+
+/// Index of a participant of a [`SimBus`]
+pub type NodeId = usize;
+
+/// Answers requests to a slave, `None` for no answer
+type Responder = Box<dyn FnMut(&Telegram) -> Option<Vec<u8>>>;
+
+struct Node {
+    driver: EbusDriver,
+    queue: TxQueue<QUEUE_LEN>,
+    tx: NodeTx,
+    /// Bytes received from the bus with the slot they are due
+    rx: VecDeque<(u64, u8)>,
+    /// Slots between a byte being on the bus and being processed by the driver
+    latency: u64,
+    /// Only answer requests to this address (for scripted slaves)
+    slave_addr: Option<u8>,
+    responder: Option<Responder>,
+    vetting: bool,
+    results: Vec<ProcessResult>,
+}
+
+/// Time of the current slot, there is no arbitration delay
+struct SimClock(Duration);
+
+impl Clock for SimClock {
+    fn now(&self) -> Duration {
+        self.0
+    }
+
+    fn delay(&self, _duration: Duration) {}
+}
+
+#[derive(Default)]
+struct NodeTx(VecDeque<u8>);
+
+impl Transmit for NodeTx {
+    type Error = core::convert::Infallible;
+
+    fn transmit_raw(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.0.extend(bytes);
+
+        Ok(())
+    }
+
+    fn clear_buffer(&mut self) -> Result<(), Self::Error> {
+        self.0.clear();
+
+        Ok(())
+    }
+}
+
+/// Shared bus connecting any number of [`EbusDriver`]s and scripted slaves
+pub struct SimBus {
+    nodes: Vec<Node>,
+    /// Bytes sent by nobody in particular (e.g. noise or a foreign participant)
+    external: VecDeque<u8>,
+    /// Idle slots before the AUTO-SYN generator sends a SYN, 0 to disable it
+    auto_syn: u32,
+    idle: u32,
+    slot: u64,
+    log: Vec<u8>,
+}
+
+impl Default for SimBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SimBus {
+    pub fn new() -> Self {
+        SimBus {
+            nodes: Vec::new(),
+            external: VecDeque::new(),
+            auto_syn: 1,
+            idle: 0,
+            slot: 0,
+            log: Vec::new(),
+        }
+    }
+
+    /// Number of idle slots before AUTO-SYN (default 1), 0 disables the generator
+    pub fn set_auto_syn(&mut self, idle_slots: u32) {
+        self.auto_syn = idle_slots;
+    }
+
+    /// Add a participant, it sends the telegrams of its [`SimBus::queue`]
+    pub fn add_node(&mut self, driver: EbusDriver) -> NodeId {
+        self.nodes.push(Node {
+            driver,
+            queue: TxQueue::new(),
+            tx: NodeTx::default(),
+            rx: VecDeque::new(),
+            latency: 0,
+            slave_addr: None,
+            responder: None,
+            vetting: false,
+            results: Vec::new(),
+        });
+
+        self.nodes.len() - 1
+    }
+
+    /// Add a slave answering requests to `addr` with `respond`
+    pub fn add_slave(
+        &mut self,
+        addr: u8,
+        respond: impl FnMut(&Telegram) -> Option<Vec<u8>> + 'static,
+    ) -> NodeId {
+        // without an own address, the driver reports every request
+        let id = self.add_node(EbusDriver::new(Duration::ZERO, 0x9B, 0x5C, 0));
+        self.nodes[id].slave_addr = Some(addr);
+        self.set_responder(id, respond);
+
+        id
+    }
+
+    /// Answer requests reported by the driver of `node`
+    pub fn set_responder(
+        &mut self,
+        node: NodeId,
+        respond: impl FnMut(&Telegram) -> Option<Vec<u8>> + 'static,
+    ) {
+        self.nodes[node].responder = Some(Box::new(respond));
+    }
+
+    /// Delay in slots until `node` sees a byte on the bus (default 0)
+    ///
+    /// With a latency, the source address is sent too late to take part in arbitration and
+    /// the reply of a slave is acknowledged too late, before AUTO-SYN.
+    pub fn set_latency(&mut self, node: NodeId, slots: u64) {
+        self.nodes[node].latency = slots;
+    }
+
+    pub fn driver(&mut self, node: NodeId) -> &mut EbusDriver {
+        &mut self.nodes[node].driver
+    }
+
+    pub fn queue(&mut self, node: NodeId) -> &mut TxQueue<QUEUE_LEN> {
+        &mut self.nodes[node].queue
+    }
+
+    /// All results of `node` except `ProcessResult::None`
+    pub fn results(&self, node: NodeId) -> &[ProcessResult] {
+        &self.nodes[node].results
+    }
+
+    pub fn take_results(&mut self, node: NodeId) -> Vec<ProcessResult> {
+        core::mem::take(&mut self.nodes[node].results)
+    }
+
+    /// Put bytes on the bus, one per slot
+    pub fn inject(&mut self, bytes: &[u8]) {
+        self.external.extend(bytes);
+    }
+
+    /// Every byte that has been on the bus
+    pub fn log(&self) -> &[u8] {
+        &self.log
+    }
+
+    /// Time since the start of the simulation
+    pub fn now(&self) -> Duration {
+        Duration::from_nanos(SLOT.as_nanos() as u64 * self.slot)
+    }
+
+    /// Simulate one slot, returns the byte on the bus if any
+    pub fn step(&mut self) -> Option<u8> {
+        let sent = self
+            .nodes
+            .iter_mut()
+            .filter_map(|node| node.tx.0.pop_front())
+            .chain(self.external.pop_front())
+            .reduce(|a, b| a & b);
+
+        let word = match sent {
+            Some(word) => Some(word),
+            None if self.auto_syn != 0 && self.idle >= self.auto_syn => Some(SYN),
+            None => None,
+        };
+        match word {
+            Some(word) => {
+                self.idle = 0;
+                self.log.push(word);
+                for node in &mut self.nodes {
+                    node.rx.push_back((self.slot + node.latency, word));
+                }
+            }
+            None => self.idle += 1,
+        }
+
+        let now = self.now();
+        for node in &mut self.nodes {
+            node.step(self.slot, now);
+        }
+        self.slot += 1;
+
+        word
+    }
+
+    /// Simulate `slots` slots
+    pub fn run(&mut self, slots: u64) {
+        for _ in 0..slots {
+            self.step();
+        }
+    }
+
+    /// Simulate until `done` returns `true`, at most `max_slots` slots.
+    /// Returns the result of `done`.
+    pub fn run_until(&mut self, max_slots: u64, mut done: impl FnMut(&mut Self) -> bool) -> bool {
+        for _ in 0..max_slots {
+            if done(self) {
+                return true;
+            }
+            self.step();
+        }
+
+        done(self)
+    }
+}
+
+impl Node {
+    fn step(&mut self, slot: u64, now: Duration) {
+        let mut received = false;
+        while let Some(&(due, word)) = self.rx.front() {
+            if due > slot {
+                break;
+            }
+            self.rx.pop_front();
+            received = true;
+
+            let is_low_latency = self.rx.front().is_none_or(|&(due, _)| due > slot);
+            let Ok(res) = self.queue.process(
+                &mut self.driver,
+                word,
+                &mut self.tx,
+                &SimClock(now),
+                is_low_latency,
+            );
+            self.vetting = matches!(res, ProcessResult::VetReply { .. });
+            self.handle(res);
+        }
+
+        if received {
+            return;
+        }
+
+        if self.vetting {
+            // the reply is complete, the vetting timeout is shorter than a slot
+            self.vetting = false;
+            let Ok(()) = self.driver.vet_timeout(&mut self.tx);
+        }
+        let Ok(()) = self.driver.poll(&mut self.tx, &SimClock(now));
+    }
+
+    fn handle(&mut self, res: ProcessResult) {
+        let ProcessResult::Request { telegram, token } = res else {
+            if !res.is_none() {
+                self.results.push(res);
+            }
+            return;
+        };
+        if self.slave_addr.is_some_and(|addr| addr != telegram.dest) {
+            // request for another scripted slave
+            return;
+        }
+
+        let data = self
+            .responder
+            .as_mut()
+            .and_then(|respond| respond(&telegram));
+        let token = match data {
+            Some(data) => {
+                let Ok(()) = self.driver.reply_as_slave(&data, &mut self.tx, token);
+                RequestToken { _priv: () }
+            }
+            None => token,
+        };
+        self.results
+            .push(ProcessResult::Request { telegram, token });
+    }
+}
@@ -0,0 +1,37 @@
+/// Counters of bus events, see [`EbusDriver::stats`](crate::EbusDriver::stats).
+///
+/// All counters saturate at `u32::MAX`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Stats {
+    /// Telegrams of other masters received completely, including those with CRC errors
+    pub telegrams_seen: u32,
+    /// Telegrams we sent, repetitions included
+    pub telegrams_sent: u32,
+    /// Arbitrations we won
+    pub arbitration_won: u32,
+    /// Arbitrations we lost, to a higher or the same priority class
+    pub arbitration_lost: u32,
+    /// SYNs we had to let pass because of the fairness counter
+    pub fairness_waits: u32,
+    /// SYN received while arbitrating
+    pub double_syn: u32,
+    /// Escape prefix followed by something other than `0x00` or `0x01`
+    pub invalid_escape: u32,
+    /// Received telegrams with a wrong CRC
+    pub telegram_crc_errors: u32,
+    /// Received replies with a wrong CRC
+    pub reply_crc_errors: u32,
+    /// Negative or invalid acknowledges for telegrams or replies we sent
+    pub nacks: u32,
+    /// Length fields above `MAX_BUF`
+    pub len_overflow: u32,
+    /// Transactions of ours that ended without an answer
+    pub timeouts: u32,
+}
+
+/// Increment a counter of [`Stats`]
+pub(crate) fn inc(counter: &mut u32) {
+    *counter = counter.saturating_add(1);
+}
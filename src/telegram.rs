@@ -3,7 +3,9 @@ use core::ops;
 use crate::MAX_BUF;
 
 /// Telegram to be sent
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MasterTelegram {
     /// Core telegram data
     pub telegram: Telegram,
@@ -12,6 +14,8 @@ pub struct MasterTelegram {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Telegram {
     /// QQ - source eBUS address
     pub src: u8,
@@ -23,6 +27,25 @@ pub struct Telegram {
     pub data: Buffer,
 }
 
+/// A complete bus transaction observed while sniffing
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Transaction {
+    /// Telegram sent by the initiating master
+    pub telegram: Telegram,
+    /// Whether the CRC of the master telegram matched
+    pub telegram_crc_ok: bool,
+    /// ACK byte of the recipient (`None` for broadcasts or if it never arrived)
+    pub slave_ack: Option<u8>,
+    /// Reply of the slave (master-slave telegrams only)
+    pub reply: Option<Buffer>,
+    /// Whether the CRC of the slave reply matched (`None` if there was no reply)
+    pub reply_crc_ok: Option<bool>,
+    /// ACK byte of the master for the slave reply
+    pub master_ack: Option<u8>,
+}
+
 #[derive(Clone, PartialEq)]
 pub struct Buffer {
     data: [u8; MAX_BUF],
@@ -65,7 +88,98 @@ impl core::fmt::Debug for Buffer {
     }
 }
 
+#[cfg(feature = "defmt")]
+impl defmt::Format for Buffer {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "{=[u8]:X}", self.as_bytes())
+    }
+}
+
+/// Hex string in human-readable formats (e.g. JSON), bytes otherwise
+#[cfg(feature = "serde")]
+impl serde::Serialize for Buffer {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if !serializer.is_human_readable() {
+            return serializer.serialize_bytes(self.as_bytes());
+        }
+
+        const HEX: &[u8; 16] = b"0123456789abcdef";
+        let mut hex = [0; 2 * MAX_BUF];
+        for (i, byte) in self.as_bytes().iter().enumerate() {
+            hex[2 * i] = HEX[(byte >> 4) as usize];
+            hex[2 * i + 1] = HEX[(byte & 0x0F) as usize];
+        }
+        let hex = core::str::from_utf8(&hex[..2 * self.as_bytes().len()]).unwrap();
+
+        serializer.serialize_str(hex)
+    }
+}
+
+/// Accepts a hex string, bytes or a sequence of numbers
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Buffer {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::{Error, SeqAccess, Unexpected, Visitor};
+
+        struct BufferVisitor;
+
+        impl<'de> Visitor<'de> for BufferVisitor {
+            type Value = Buffer;
+
+            fn expecting(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+                write!(f, "at most {MAX_BUF} bytes as hex string or byte array")
+            }
+
+            fn visit_str<E: Error>(self, v: &str) -> Result<Buffer, E> {
+                if !v.bytes().all(|c| c.is_ascii_hexdigit()) {
+                    return Err(E::invalid_value(Unexpected::Str(v), &self));
+                }
+                if !v.len().is_multiple_of(2) || v.len() > 2 * MAX_BUF {
+                    return Err(E::invalid_length(v.len() / 2, &self));
+                }
+
+                let mut data = [0; MAX_BUF];
+                for (byte, hex) in data.iter_mut().zip(v.as_bytes().chunks(2)) {
+                    let hex = core::str::from_utf8(hex).unwrap();
+                    *byte = u8::from_str_radix(hex, 16).unwrap();
+                }
+
+                Ok(Buffer::from_parts(data, (v.len() / 2) as u8))
+            }
+
+            fn visit_bytes<E: Error>(self, v: &[u8]) -> Result<Buffer, E> {
+                if v.len() > MAX_BUF {
+                    return Err(E::invalid_length(v.len(), &self));
+                }
+
+                Ok(Buffer::from_slice(v))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Buffer, A::Error> {
+                let mut data = [0; MAX_BUF];
+                let mut len = 0;
+                while let Some(byte) = seq.next_element()? {
+                    *data
+                        .get_mut(len)
+                        .ok_or_else(|| A::Error::invalid_length(len + 1, &self))? = byte;
+                    len += 1;
+                }
+
+                Ok(Buffer::from_parts(data, len as u8))
+            }
+        }
+
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_any(BufferVisitor)
+        } else {
+            deserializer.deserialize_bytes(BufferVisitor)
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum TelegramFlag {
     /// Whether the data is expected to have an additional CRC prepended
@@ -91,6 +205,7 @@ impl ops::BitOr<TelegramFlags> for TelegramFlag {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct TelegramFlags(u8);
 
 impl TelegramFlags {
@@ -99,6 +214,52 @@ impl TelegramFlags {
     }
 }
 
+/// List of the flags that are set, e.g. `["NeedsDataCrc", "ExpectReply"]`
+#[cfg(feature = "serde")]
+impl serde::Serialize for TelegramFlags {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeSeq;
+
+        let flags = [TelegramFlag::NeedsDataCrc, TelegramFlag::ExpectReply]
+            .into_iter()
+            .filter(|&flag| *self & flag);
+        let mut seq = serializer.serialize_seq(Some(flags.clone().count()))?;
+        for flag in flags {
+            seq.serialize_element(&flag)?;
+        }
+
+        seq.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for TelegramFlags {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::{SeqAccess, Visitor};
+
+        struct FlagsVisitor;
+
+        impl<'de> Visitor<'de> for FlagsVisitor {
+            type Value = TelegramFlags;
+
+            fn expecting(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+                f.write_str("list of telegram flags")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<TelegramFlags, A::Error> {
+                let mut flags = TelegramFlags::none();
+                while let Some(flag) = seq.next_element::<TelegramFlag>()? {
+                    flags = flags | flag;
+                }
+
+                Ok(flags)
+            }
+        }
+
+        deserializer.deserialize_seq(FlagsVisitor)
+    }
+}
+
 impl ops::BitAnd<TelegramFlag> for TelegramFlags {
     type Output = bool;
 
@@ -0,0 +1,178 @@
+#![cfg(feature = "async")]
+
+use std::{
+    collections::VecDeque,
+    future::{poll_fn, Future},
+    pin::pin,
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+use ebus::{
+    asynch::{AsyncEbus, Event, Outcome},
+    Buffer, Clock, Crc, EbusDriver, MasterTelegram, Telegram, TelegramFlag, TelegramFlags,
+};
+use embedded_hal_async::delay::DelayNs;
+use embedded_io_async::{ErrorType, Read, Write};
+
+/// UART echoing everything written, then playing back a script
+#[derive(Default)]
+struct MockUart {
+    loopback: VecDeque<u8>,
+    script: VecDeque<u8>,
+    written: Vec<u8>,
+}
+
+impl ErrorType for MockUart {
+    type Error = embedded_io_async::ErrorKind;
+}
+
+impl Read for MockUart {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        poll_fn(|_| {
+            match self
+                .loopback
+                .pop_front()
+                .or_else(|| self.script.pop_front())
+            {
+                Some(byte) => {
+                    buf[0] = byte;
+                    Poll::Ready(Ok(1))
+                }
+                // idle bus
+                None => Poll::Pending,
+            }
+        })
+        .await
+    }
+}
+
+impl Write for MockUart {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.loopback.extend(buf);
+        self.written.extend(buf);
+
+        Ok(buf.len())
+    }
+}
+
+struct NoDelay;
+
+impl DelayNs for NoDelay {
+    async fn delay_ns(&mut self, _ns: u32) {}
+}
+
+impl Clock for NoDelay {
+    fn now(&self) -> Duration {
+        Duration::ZERO
+    }
+
+    fn delay(&self, _duration: Duration) {}
+}
+
+fn block_on<F: Future>(fut: F) -> F::Output {
+    let mut fut = pin!(fut);
+    let mut cx = Context::from_waker(Waker::noop());
+    for _ in 0..10_000 {
+        if let Poll::Ready(res) = fut.as_mut().poll(&mut cx) {
+            return res;
+        }
+    }
+
+    panic!("future did not complete");
+}
+
+fn driver() -> EbusDriver {
+    let mut driver = EbusDriver::new(Duration::from_micros(123), 0x9B, 0x5C, 0);
+    driver.set_master_address(0x10).unwrap();
+
+    driver
+}
+
+#[test]
+fn send_with_reply() {
+    let uart = MockUart {
+        // SYN, then ACK and reply of the slave
+        script: [0xAA, 0x00, 0x02, 0xA9, 0x00, 0xDA, 0x82].into(),
+        ..Default::default()
+    };
+    let mut ebus = AsyncEbus::new(driver(), uart, NoDelay, NoDelay);
+    let msg = MasterTelegram {
+        telegram: Telegram {
+            src: 0xFF,
+            dest: 0x51,
+            service: 0x5022,
+            data: Buffer::from_slice(&[15, 0]),
+        },
+        flags: TelegramFlag::NeedsDataCrc | TelegramFlag::ExpectReply,
+    };
+
+    let outcome = block_on(ebus.send(&msg)).unwrap();
+    assert_eq!(
+        outcome,
+        Outcome::Reply {
+            data: Buffer::from_slice(&[0xA9, 0xDA]),
+            attempts: 1
+        }
+    );
+}
+
+#[test]
+fn request_and_reply() {
+    let mut telegram = vec![0x03, 0x15, 0x07, 0x05, 0x00];
+    telegram.push(Crc::new(0x9B).add_multiple(&telegram).calc_crc());
+    let mut script = vec![0xAA];
+    script.extend(telegram);
+
+    let uart = MockUart {
+        script: script.into(),
+        ..Default::default()
+    };
+    let mut ebus = AsyncEbus::new(driver(), uart, NoDelay, NoDelay);
+
+    let request = block_on(ebus.next_request()).unwrap();
+    assert_eq!(request.telegram.service, 0x0705);
+    block_on(ebus.reply(request, &[0x01])).unwrap();
+
+    let (_, uart, _, _) = ebus.into_inner();
+    let crc = Crc::new(0x9B).add_multiple(&[0x01, 0x01]).calc_crc();
+    assert_eq!(uart.written, [0x00, 0x01, 0x01, crc]);
+}
+
+#[test]
+fn request_while_sending() {
+    let mut telegram = vec![0x03, 0x15, 0x07, 0x05, 0x00];
+    telegram.push(Crc::new(0x9B).add_multiple(&telegram).calc_crc());
+    let mut script = telegram;
+    // ACK of the master for our reply, SYN, ACK of the slave for our telegram
+    script.extend([0x00, 0xAA, 0x00]);
+
+    let uart = MockUart {
+        script: script.into(),
+        ..Default::default()
+    };
+    let mut ebus = AsyncEbus::new(driver(), uart, NoDelay, NoDelay);
+    let msg = MasterTelegram {
+        telegram: Telegram {
+            src: 0x10,
+            dest: 0x52,
+            service: 0x0700,
+            data: Buffer::from_slice(&[]),
+        },
+        flags: TelegramFlags::none(),
+    };
+
+    let Event::Request(request) = block_on(ebus.run(Some(&msg))).unwrap() else {
+        panic!("expected request");
+    };
+    assert_eq!(request.telegram.src, 0x03);
+    block_on(ebus.reply(request, &[0x01])).unwrap();
+
+    match block_on(ebus.run(Some(&msg))).unwrap() {
+        Event::Sent(outcome) => assert_eq!(outcome, Outcome::Acked { attempts: 1 }),
+        other => panic!("{other:?}"),
+    }
+    let (_, uart, _, _) = ebus.into_inner();
+    assert_eq!(&uart.written[..2], [0x00, 0x01]);
+    assert_eq!(uart.written[4], 0x10);
+}
@@ -0,0 +1,184 @@
+mod helper;
+
+use std::time::Duration;
+
+use ebus::{
+    enhanced::{AdapterTransmit, Command, Decoder, Response},
+    Buffer, EbusDriver, MasterTelegram, ProcessResult, Telegram, TelegramFlags,
+};
+use helper::{BusTransmitter, NoClock};
+
+/// Adapter on an otherwise idle bus, everything sent is received back
+#[derive(Default)]
+struct FakeAdapter {
+    /// Bytes for the host
+    out: Vec<u8>,
+    /// Master address to arbitrate for on the next SYN
+    start: Option<u8>,
+    /// Lose the next arbitration against this address
+    lose_to: Option<u8>,
+    first: Option<u8>,
+    /// Every command received
+    commands: Vec<Command>,
+}
+
+impl FakeAdapter {
+    fn respond(&mut self, response: Response) {
+        let (cmd, data) = match response {
+            Response::Received(data) if data < 0x80 => {
+                self.out.push(data);
+                return;
+            }
+            Response::Received(data) => (0x1, data),
+            Response::Started(data) => (0x2, data),
+            Response::Failed(data) => (0xA, data),
+            other => unimplemented!("{other:?}"),
+        };
+        self.out
+            .extend([0xC0 | cmd << 2 | data >> 6, 0x80 | (data & 0x3F)]);
+    }
+
+    /// Byte on the bus
+    fn bus(&mut self, byte: u8) {
+        self.respond(Response::Received(byte));
+        if byte != 0xAA {
+            return;
+        }
+        if let Some(src) = self.start.take() {
+            match self.lose_to.take() {
+                Some(winner) => self.respond(Response::Failed(winner)),
+                None => self.respond(Response::Started(src)),
+            }
+        }
+    }
+
+    /// Byte from the host
+    fn host(&mut self, byte: u8) {
+        let command = match (self.first.take(), byte) {
+            (None, byte) if byte < 0x80 => Command::Send(byte),
+            (None, byte) => {
+                self.first = Some(byte);
+                return;
+            }
+            (Some(first), byte) => {
+                let data = first << 6 | (byte & 0x3F);
+                match (first >> 2) & 0x0F {
+                    0x1 => Command::Send(data),
+                    0x2 => Command::Start(data),
+                    other => unimplemented!("command {other}"),
+                }
+            }
+        };
+        self.commands.push(command);
+        match command {
+            Command::Send(byte) => self.bus(byte),
+            Command::Start(src) => self.start = Some(src),
+            _ => {}
+        }
+    }
+}
+
+struct Setup {
+    driver: EbusDriver,
+    tx: AdapterTransmit<BusTransmitter>,
+    decoder: Decoder,
+    adapter: FakeAdapter,
+}
+
+impl Setup {
+    fn new() -> Self {
+        let mut driver = EbusDriver::new(Duration::from_micros(123), 0x9B, 0x5C, 0);
+        driver.set_master_address(0x10).unwrap();
+        driver.set_adapter_arbitration(true);
+
+        Setup {
+            driver,
+            tx: AdapterTransmit(BusTransmitter::default()),
+            decoder: Decoder::new(),
+            adapter: FakeAdapter::default(),
+        }
+    }
+
+    /// Put `bytes` on the bus and run until host and adapter are done talking
+    fn bus(&mut self, bytes: &[u8], msg: Option<&MasterTelegram>) -> Vec<ProcessResult> {
+        bytes.iter().for_each(|&byte| self.adapter.bus(byte));
+
+        let mut results = vec![];
+        while !self.adapter.out.is_empty() {
+            for byte in std::mem::take(&mut self.adapter.out) {
+                let Some(response) = self.decoder.feed(byte) else {
+                    continue;
+                };
+                let res = self
+                    .driver
+                    .process_response(response.unwrap(), &mut self.tx, &NoClock, msg)
+                    .unwrap();
+                results.push(res);
+            }
+            for byte in std::mem::take(&mut self.tx.0.sent) {
+                self.adapter.host(byte);
+            }
+        }
+
+        results
+    }
+}
+
+fn msg() -> MasterTelegram {
+    MasterTelegram {
+        telegram: Telegram {
+            src: 0x10,
+            dest: 0x30,
+            service: 0x0705,
+            data: Buffer::from_slice(&[0xAA]),
+        },
+        flags: TelegramFlags::none(),
+    }
+}
+
+#[test]
+fn arbitration_won() {
+    let mut s = Setup::new();
+    let msg = msg();
+
+    s.bus(&[0xAA], Some(&msg));
+    assert_eq!(s.adapter.commands, [Command::Start(0x10)]);
+    assert!(s.driver.is_sending());
+
+    s.bus(&[0xAA], Some(&msg));
+    // escaped data byte and CRC, sent as two-byte symbols where needed
+    let sent: Vec<_> = s.adapter.commands[1..].to_vec();
+    assert_eq!(
+        sent,
+        [0x30, 0x07, 0x05, 0x01, 0xA9, 0x01, 0x8D].map(Command::Send)
+    );
+
+    let res = s.bus(&[0x00], Some(&msg));
+    assert_eq!(res[0], ProcessResult::MasterAckOk { attempts: 1 });
+    // bus released with SYN, then arbitration starts again as we keep offering `msg`
+    assert_eq!(
+        s.adapter.commands[8..],
+        [Command::Send(0xAA), Command::Start(0x10)]
+    );
+    assert_eq!(s.driver.stats().arbitration_won, 1);
+}
+
+#[test]
+fn arbitration_lost() {
+    let mut s = Setup::new();
+    let msg = msg();
+
+    s.bus(&[0xAA], Some(&msg));
+    s.adapter.lose_to = Some(0x03);
+    s.bus(&[0xAA], Some(&msg));
+    assert_eq!(s.driver.stats().arbitration_lost, 1);
+    assert!(!s.driver.is_sending());
+
+    // rest of the winner's telegram, then the fairness counter holds us back for two SYNs
+    s.bus(&[0x30, 0x07, 0x05, 0x00], None);
+    s.bus(&[0xAA, 0xAA], Some(&msg));
+    assert_eq!(s.adapter.commands, [Command::Start(0x10)]);
+
+    s.bus(&[0xAA], Some(&msg));
+    assert_eq!(s.adapter.commands, [Command::Start(0x10); 2]);
+}
@@ -1,10 +1,10 @@
 #![allow(dead_code)]
 
-use std::{iter::once, time::Duration};
+use std::{cell::Cell, iter::once, time::Duration};
 
 use ebus::{
-    Buffer, Crc, EbusDriver, MasterTelegram, ProcessResult, RequestToken, Telegram, TelegramFlag,
-    Transmit,
+    service::Identification, Buffer, BusEvent, Clock, Crc, EbusDriver, MasterTelegram,
+    ProcessResult, RequestToken, Stats, Telegram, TelegramFlag, Transmit,
 };
 
 #[derive(Default)]
@@ -33,7 +33,54 @@ pub struct AutoLoopback {
     transmit: LoopbackTransmitter,
 }
 
-fn sleep(_: Duration) {}
+/// Collects the bytes sent by a driver
+#[derive(Default)]
+pub struct BusTransmitter {
+    pub sent: Vec<u8>,
+}
+
+impl Transmit for BusTransmitter {
+    type Error = ();
+
+    fn clear_buffer(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn transmit_raw(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.sent.extend_from_slice(bytes);
+
+        Ok(())
+    }
+}
+
+/// Time stands still, no arbitration delay
+pub struct NoClock;
+
+impl Clock for NoClock {
+    fn now(&self) -> Duration {
+        Duration::ZERO
+    }
+
+    fn delay(&self, _duration: Duration) {}
+}
+
+/// Time only advances when told to, no arbitration delay
+#[derive(Default)]
+pub struct ByteClock(Cell<Duration>);
+
+impl ByteClock {
+    pub fn advance(&self, d: Duration) {
+        self.0.set(self.0.get() + d);
+    }
+}
+
+impl Clock for ByteClock {
+    fn now(&self) -> Duration {
+        self.0.get()
+    }
+
+    fn delay(&self, _duration: Duration) {}
+}
 
 impl AutoLoopback {
     pub fn new() -> Self {
@@ -49,10 +96,31 @@ impl AutoLoopback {
         this
     }
 
+    pub fn sniffer() -> Self {
+        let mut this = Self::new();
+        this.driver.set_sniffing(true);
+
+        this
+    }
+
+    pub fn set_master_address(&mut self, addr: u8) {
+        self.driver.set_master_address(addr).unwrap();
+    }
+
+    pub fn set_observe(&mut self, observe: bool) {
+        self.driver.set_observe(observe);
+    }
+
+    pub fn set_identification(&mut self, identification: &Identification) {
+        self.driver
+            .set_identification(Some(identification))
+            .unwrap();
+    }
+
     pub fn process(&mut self, byte: u8, msg: Option<&MasterTelegram>) -> Vec<ProcessResult> {
         let mut results = vec![self
             .driver
-            .process(byte, &mut self.transmit, sleep, msg, true)
+            .process(byte, &mut self.transmit, &NoClock, msg, true)
             .unwrap()];
 
         results.extend(self.process_bus(msg));
@@ -78,7 +146,7 @@ impl AutoLoopback {
                 .map(|byte| {
                     // caveat: we are not dropping our message, even when it was already sent / timed out
                     self.driver
-                        .process(byte, &mut self.transmit, sleep, msg, true)
+                        .process(byte, &mut self.transmit, &NoClock, msg, true)
                         .unwrap()
                 });
             results.extend(iter);
@@ -87,6 +155,26 @@ impl AutoLoopback {
         panic!("infinite loop detected");
     }
 
+    pub fn drain_events(&mut self) -> Vec<BusEvent> {
+        self.driver.drain_events().collect()
+    }
+
+    pub fn stats(&self) -> &Stats {
+        self.driver.stats()
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.driver.reset_stats();
+    }
+
+    pub fn sent(&self) -> &[u8] {
+        &self.transmit.sent
+    }
+
+    pub fn sent_len(&self) -> usize {
+        self.transmit.sent.len()
+    }
+
     pub fn last_sent(&self) -> Option<u8> {
         self.transmit.sent.last().cloned()
     }
@@ -112,24 +200,20 @@ impl AutoLoopback {
 
         let msg = &tele.telegram;
 
-        let mut v: Vec<u8> = [
-            msg.src,
-            msg.dest,
-            (msg.service & 0xFF) as u8,
-            (msg.service >> 8) as u8,
-        ]
-        .into_iter()
-        .chain(once(
-            msg.data.as_bytes().len() as u8 + (tele.flags & TelegramFlag::NeedsDataCrc) as u8,
-        ))
-        .chain(if tele.flags & TelegramFlag::NeedsDataCrc {
-            Some(Crc::new(0x5C).add_multiple(msg.data.as_bytes()).calc_crc())
-        } else {
-            None
-        })
-        .chain(msg.data.as_bytes().iter().cloned())
-        .flat_map(|byte| escape(byte))
-        .collect();
+        let svc = msg.service.to_be_bytes();
+        let mut v: Vec<u8> = [msg.src, msg.dest, svc[0], svc[1]]
+            .into_iter()
+            .chain(once(
+                msg.data.as_bytes().len() as u8 + (tele.flags & TelegramFlag::NeedsDataCrc) as u8,
+            ))
+            .chain(if tele.flags & TelegramFlag::NeedsDataCrc {
+                Some(Crc::new(0x5C).add_multiple(msg.data.as_bytes()).calc_crc())
+            } else {
+                None
+            })
+            .chain(msg.data.as_bytes().iter().cloned())
+            .flat_map(escape)
+            .collect();
 
         let crc = Crc::new(0x9B).add_multiple(&v).calc_crc();
         v.extend(escape(crc));
@@ -140,12 +224,30 @@ impl AutoLoopback {
         self.send_external_bytes(&v);
     }
 
+    /// Sends ACK, the reply and its CRC like a slave would
+    pub fn send_external_reply(&mut self, data: &[u8]) {
+        let mut v = vec![data.len() as u8];
+        v.extend_from_slice(data);
+        let mut v: Vec<u8> = v.into_iter().flat_map(escape).collect();
+
+        let crc = Crc::new(0x9B).add_multiple(&v).calc_crc();
+        v.extend(escape(crc));
+
+        self.send_external_bytes(&[0x00]);
+        self.send_external_bytes(&v);
+    }
+
     pub fn reply_as_slave(&mut self, data: &[u8], token: RequestToken) {
         self.driver
             .reply_as_slave(data, &mut self.transmit, token)
             .unwrap();
     }
 
+    pub fn vet_timeout(&mut self, msg: Option<&MasterTelegram>) -> Vec<ProcessResult> {
+        self.driver.vet_timeout(&mut self.transmit).unwrap();
+        self.process_bus(msg)
+    }
+
     pub fn reply_ack(&mut self, token: RequestToken) {
         self.driver.reply_ack(&mut self.transmit, token).unwrap();
     }
@@ -0,0 +1,233 @@
+#![cfg(feature = "tokio")]
+
+use std::time::Duration;
+
+use ebus::{
+    enhanced::{Command, Decoder, Response},
+    host::HostDriver,
+    Buffer, Crc, EbusDriver, MasterTelegram, Outcome, ProcessResult, Telegram, TelegramFlag,
+    TelegramFlags,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt, DuplexStream},
+    time,
+};
+
+/// Echoes everything the driver writes, sends the next chunk of `script` whenever the bus
+/// is idle
+async fn bus(mut io: DuplexStream, script: Vec<Vec<u8>>) {
+    let mut script = script.into_iter();
+    let mut buf = [0; 64];
+
+    loop {
+        match time::timeout(Duration::from_millis(20), io.read(&mut buf)).await {
+            Ok(Ok(0)) | Ok(Err(_)) => return,
+            Ok(Ok(n)) => io.write_all(&buf[..n]).await.unwrap(),
+            Err(_) => match script.next() {
+                Some(chunk) => io.write_all(&chunk).await.unwrap(),
+                None => return,
+            },
+        }
+    }
+}
+
+/// Like `bus`, but speaks the enhanced protocol and wins every arbitration.
+///
+/// SEND / START use the same codes as RECEIVED / STARTED, so `Command` and `Decoder` work
+/// in both directions.
+async fn enhanced_bus(mut io: DuplexStream, script: Vec<Vec<u8>>) {
+    let mut script = script.into_iter();
+    let mut decoder = Decoder::new();
+    let mut start = None;
+    let mut buf = [0; 64];
+    let mut out = [0; 2];
+
+    loop {
+        match time::timeout(Duration::from_millis(20), io.read(&mut buf)).await {
+            Ok(Ok(0)) | Ok(Err(_)) => return,
+            Ok(Ok(n)) => {
+                for &byte in &buf[..n] {
+                    match decoder.feed(byte) {
+                        Some(Ok(Response::Received(byte))) => {
+                            let echo = Command::Send(byte).encode(&mut out);
+                            io.write_all(echo).await.unwrap();
+                        }
+                        Some(Ok(Response::Started(src))) => start = Some(src),
+                        None => {}
+                        other => panic!("unexpected command {other:?}"),
+                    }
+                }
+            }
+            Err(_) => match script.next() {
+                Some(chunk) => {
+                    for byte in chunk {
+                        io.write_all(Command::Send(byte).encode(&mut out))
+                            .await
+                            .unwrap();
+                        if let Some(src) = start.take().filter(|_| byte == 0xAA) {
+                            let started = Command::Start(src).encode(&mut out);
+                            io.write_all(started).await.unwrap();
+                        }
+                    }
+                }
+                None => return,
+            },
+        }
+    }
+}
+
+fn driver() -> EbusDriver {
+    let mut driver = EbusDriver::new(Duration::from_micros(123), 0x9B, 0x5C, 0);
+    driver.set_master_address(0x10).unwrap();
+
+    driver
+}
+
+#[tokio::test]
+async fn send_master_master() {
+    let (io, bus_io) = tokio::io::duplex(64);
+    let host = HostDriver::new(driver(), io);
+    let handle = host.handle();
+    let mut events = handle.subscribe();
+
+    let sent = handle.send(MasterTelegram {
+        telegram: Telegram {
+            src: 0x10,
+            dest: 0x30,
+            service: 0x0705,
+            data: Buffer::from_slice(&[0x01]),
+        },
+        flags: TelegramFlags::none(),
+    });
+    tokio::spawn(host.run());
+    tokio::spawn(bus(bus_io, vec![vec![0xAA], vec![0x00]]));
+
+    assert_eq!(sent.await.unwrap(), Outcome::Acked { attempts: 1 });
+    loop {
+        if let ProcessResult::MasterAckOk { .. } = *events.recv().await.unwrap() {
+            break;
+        }
+    }
+}
+
+#[tokio::test]
+async fn send_with_reply() {
+    let (io, bus_io) = tokio::io::duplex(64);
+    let host = HostDriver::new(driver(), io);
+    let handle = host.handle();
+    tokio::spawn(host.run());
+
+    let sent = handle.clone().send(MasterTelegram {
+        telegram: Telegram {
+            src: 0xFF,
+            dest: 0x51,
+            service: 0x5022,
+            data: Buffer::from_slice(&[15, 0]),
+        },
+        flags: TelegramFlag::NeedsDataCrc | TelegramFlag::ExpectReply,
+    });
+    // SYN, then ACK and reply of the slave
+    tokio::spawn(bus(
+        bus_io,
+        vec![vec![0xAA], vec![0x00, 0x02, 0xA9, 0x00, 0xDA, 0x82]],
+    ));
+
+    assert_eq!(
+        sent.await.unwrap(),
+        Outcome::Reply {
+            data: Buffer::from_slice(&[0xA9, 0xDA]),
+            attempts: 1
+        }
+    );
+}
+
+#[tokio::test]
+async fn sniff_own_transaction() {
+    let (io, bus_io) = tokio::io::duplex(64);
+    let mut host = HostDriver::new(driver(), io);
+    host.set_sniffer(Some(EbusDriver::new(Duration::ZERO, 0x9B, 0x5C, 0)));
+    let handle = host.handle();
+    let mut events = handle.subscribe();
+    tokio::spawn(host.run());
+
+    let sent = handle.send(MasterTelegram {
+        telegram: Telegram {
+            src: 0x10,
+            dest: 0x15,
+            service: 0x0704,
+            data: Buffer::from_slice(&[]),
+        },
+        flags: TelegramFlag::ExpectReply | TelegramFlags::none(),
+    });
+    // SYN, ACK and reply of the slave, SYN after our ACK
+    let crc = Crc::new(0x9B).add_multiple(&[0x01, 0x42]).calc_crc();
+    tokio::spawn(bus(
+        bus_io,
+        vec![vec![0xAA], vec![0x00, 0x01, 0x42, crc], vec![0xAA]],
+    ));
+    assert!(sent.await.is_ok());
+
+    let transaction = loop {
+        if let ProcessResult::Transaction { transaction } = &*events.recv().await.unwrap() {
+            break transaction.clone();
+        }
+    };
+    assert_eq!(transaction.telegram.src, 0x10);
+    assert_eq!(transaction.telegram.dest, 0x15);
+    assert_eq!(transaction.slave_ack, Some(0x00));
+    assert_eq!(transaction.reply, Some(Buffer::from_slice(&[0x42])));
+    assert_eq!(transaction.reply_crc_ok, Some(true));
+    assert_eq!(transaction.master_ack, Some(0x00));
+}
+
+#[tokio::test]
+async fn closed() {
+    let (io, bus_io) = tokio::io::duplex(64);
+    let host = HostDriver::new(driver(), io);
+    let handle = host.handle();
+    drop(bus_io);
+    host.run().await.unwrap();
+
+    let msg = ebus::service::Identification::request(0x10, 0x15);
+    assert!(handle.send(msg).await.is_err());
+}
+
+#[tokio::test]
+async fn auto_syn() {
+    let (io, mut bus_io) = tokio::io::duplex(64);
+    let mut host = HostDriver::new(driver(), io);
+    host.driver().set_auto_syn(true);
+    tokio::spawn(host.run());
+
+    let mut byte = [0];
+    time::timeout(Duration::from_millis(500), bus_io.read_exact(&mut byte))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(byte, [0xAA]);
+}
+
+#[tokio::test]
+async fn enhanced_adapter() {
+    let (io, bus_io) = tokio::io::duplex(64);
+    let host = HostDriver::new_enhanced(driver(), io);
+    let handle = host.handle();
+    tokio::spawn(host.run());
+
+    let sent = handle.send(MasterTelegram {
+        telegram: Telegram {
+            src: 0x10,
+            dest: 0x30,
+            service: 0x0705,
+            data: Buffer::from_slice(&[0xAA]),
+        },
+        flags: TelegramFlags::none(),
+    });
+    // the first SYN makes us request arbitration, the adapter arbitrates on the second
+    tokio::spawn(enhanced_bus(
+        bus_io,
+        vec![vec![0xAA], vec![0xAA], vec![0x00]],
+    ));
+
+    assert_eq!(sent.await.unwrap(), Outcome::Acked { attempts: 1 });
+}
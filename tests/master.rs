@@ -1,35 +1,16 @@
 use std::time::Duration;
 
 use ebus::{
-    Buffer, Crc, EbusDriver, MasterTelegram, ProcessResult, Telegram, TelegramFlag, Transmit,
+    Buffer, BusEvent, Crc, EbusDriver, MasterTelegram, ProcessResult, Telegram, TelegramFlag,
+    TelegramFlags,
 };
 
-use crate::helper::AutoLoopback;
+use crate::helper::{AutoLoopback, BusTransmitter, ByteClock, NoClock};
 
 mod helper;
 
-struct TestTransmitter {
-    sent: Vec<u8>,
-}
-
-impl Transmit for TestTransmitter {
-    type Error = ();
-
-    fn clear_buffer(&mut self) -> Result<(), Self::Error> {
-        Ok(())
-    }
-
-    fn transmit_raw(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
-        self.sent.extend_from_slice(bytes);
-
-        Ok(())
-    }
-}
-
-fn sleep(_: Duration) {}
-
 fn test_send_and_reply_raw(tel: MasterTelegram, reply: &[u8]) -> ProcessResult {
-    let mut transmitter = TestTransmitter { sent: vec![] };
+    let mut transmitter = BusTransmitter::default();
     let msg = tel;
 
     let mut driver = EbusDriver::new(Duration::from_micros(123), 0x9B, 0x5C, 0);
@@ -37,12 +18,12 @@ fn test_send_and_reply_raw(tel: MasterTelegram, reply: &[u8]) -> ProcessResult {
     // deal with fairness counter
     for _ in 0..50 {
         driver
-            .process(0xAA, &mut transmitter, sleep, Some(&msg), true)
+            .process(0xAA, &mut transmitter, &NoClock, Some(&msg), true)
             .unwrap();
     }
     transmitter.sent.clear();
     driver
-        .process(0xAA, &mut transmitter, sleep, Some(&msg), true)
+        .process(0xAA, &mut transmitter, &NoClock, Some(&msg), true)
         .unwrap();
 
     loop {
@@ -52,7 +33,7 @@ fn test_send_and_reply_raw(tel: MasterTelegram, reply: &[u8]) -> ProcessResult {
 
         let word = transmitter.sent.remove(0);
         driver
-            .process(word, &mut transmitter, sleep, Some(&msg), true)
+            .process(word, &mut transmitter, &NoClock, Some(&msg), true)
             .unwrap();
     }
 
@@ -60,7 +41,17 @@ fn test_send_and_reply_raw(tel: MasterTelegram, reply: &[u8]) -> ProcessResult {
     let mut res = ProcessResult::None;
     for &reply_byte in reply {
         res = driver
-            .process(reply_byte, &mut transmitter, sleep, Some(&msg), true)
+            .process(reply_byte, &mut transmitter, &NoClock, Some(&msg), true)
+            .unwrap();
+    }
+
+    if let ProcessResult::VetReply { .. } = res {
+        // no garbage after the reply, acknowledge it
+        transmitter.sent.clear();
+        driver.vet_timeout(&mut transmitter).unwrap();
+        let ack = transmitter.sent.remove(0);
+        res = driver
+            .process(ack, &mut transmitter, &NoClock, Some(&msg), true)
             .unwrap();
     }
 
@@ -157,11 +148,136 @@ fn test_example1_auto_lb() {
     let res = d.process(0xAA, Some(&msg));
     assert_eq!(res.len(), 10);
     let res = d.process_multiple(&[0x00, 0x02, 0xA9, 0x00, 0xDA, 0x82], Some(&msg));
+    assert!(matches!(dbg!(&res[5][..]), [VetReply { .. }]));
+    let res = d.vet_timeout(Some(&msg));
     assert!(
-        matches!(dbg!(&res[5][..]), [.., Reply { data }, None, None] if data.as_bytes() == &[0xA9, 0xDA])
+        matches!(dbg!(&res[..]), [Reply { data, clean: true, attempts: 1 }, None] if data.as_bytes() == [0xA9, 0xDA])
     );
 }
 
+#[test]
+fn test_broadcast_no_ack() {
+    use ProcessResult::*;
+
+    let mut d = AutoLoopback::new();
+    let msg = MasterTelegram {
+        telegram: Telegram {
+            src: 0x10,
+            dest: 0xFE,
+            service: 0x0700,
+            data: Buffer::from_slice(&[0x00, 0x08, 0x35, 0x14, 0x18, 0x10, 0x03, 0x23]),
+        },
+        flags: TelegramFlags::none(),
+    };
+    let res = d.process(0xAA, Some(&msg));
+    assert!(matches!(dbg!(&res[..]), [.., BroadcastSent, None]));
+    // SYN right after the CRC
+    assert_eq!(d.last_sent(), Some(0xAA));
+}
+
+#[test]
+fn test_master_master_no_reply() {
+    use ProcessResult::*;
+
+    let mut d = AutoLoopback::new();
+    let msg = MasterTelegram {
+        telegram: Telegram {
+            src: 0xFF,
+            dest: 0x30,
+            service: 0x0502,
+            data: Buffer::from_slice(&[0x01]),
+        },
+        // ignored for master addresses
+        flags: TelegramFlag::ExpectReply | TelegramFlags::none(),
+    };
+
+    d.process(0xAA, Some(&msg));
+    let res = d.process(0x00, Some(&msg));
+    assert!(matches!(
+        dbg!(&res[..]),
+        [MasterAckOk { attempts: 1 }, None]
+    ));
+}
+
+#[test]
+fn test_repeat_after_nack() {
+    use ProcessResult::*;
+
+    let mut d = AutoLoopback::new();
+    let mut msg = example1();
+    msg.flags = TelegramFlags::none();
+
+    d.process(0xAA, Some(&msg));
+    let sent = d.sent_len();
+
+    // NACK: telegram is repeated within the same lock, starting with QQ
+    let res = d.process(0xFF, Some(&msg));
+    assert!(res.iter().all(ProcessResult::is_none));
+    assert_eq!(d.sent_len(), sent * 2);
+
+    let res = d.process(0x00, Some(&msg));
+    assert!(matches!(
+        dbg!(&res[..]),
+        [MasterAckOk { attempts: 2 }, None]
+    ));
+}
+
+#[test]
+fn test_give_up_after_second_nack() {
+    use ProcessResult::*;
+
+    let mut d = AutoLoopback::new();
+    let mut msg = example1();
+    msg.flags = TelegramFlags::none();
+
+    d.process(0xAA, Some(&msg));
+    d.process(0xFF, Some(&msg));
+    let res = d.process(0xFF, Some(&msg));
+    assert!(matches!(dbg!(&res[..]), [MasterAckErr { attempts: 2 }]));
+}
+
+#[test]
+fn test_reply_repeated_after_crc_error() {
+    use ProcessResult::*;
+
+    let mut d = AutoLoopback::new();
+    let msg = example1();
+    d.process(0xAA, Some(&msg));
+
+    // broken CRC, we answer with NACK
+    let res = d.process_multiple(&[0x00, 0x02, 0xA9, 0x00, 0xDA, 0x83], Some(&msg));
+    assert!(res.iter().flatten().all(ProcessResult::is_none));
+    assert_eq!(d.last_sent(), Some(0xFF));
+
+    let res = d.process_multiple(&[0x02, 0xA9, 0x00, 0xDA, 0x82], Some(&msg));
+    assert!(matches!(dbg!(&res[4][..]), [VetReply { .. }]));
+    let res = d.vet_timeout(Some(&msg));
+    assert!(matches!(dbg!(&res[..]), [Reply { attempts: 2, .. }, None]));
+}
+
+#[test]
+fn test_invalid_source() {
+    let mut d = AutoLoopback::new();
+    let mut msg = example1();
+    msg.telegram.src = 0xA9;
+
+    let res = d.process(0xAA, Some(&msg));
+    assert!(matches!(dbg!(&res[..]), [ProcessResult::InvalidSource]));
+    assert_eq!(d.last_sent(), None);
+
+    // reported once while the same telegram is offered
+    for _ in 0..10 {
+        let res = d.process(0xAA, Some(&msg));
+        assert!(matches!(dbg!(&res[..]), [ProcessResult::None]));
+    }
+    assert_eq!(d.drain_events(), [BusEvent::InvalidSource { src: 0xA9 }]);
+
+    // and again once it has been withdrawn
+    d.process(0xAA, None);
+    let res = d.process(0xAA, Some(&msg));
+    assert!(matches!(dbg!(&res[..]), [ProcessResult::InvalidSource]));
+}
+
 #[test]
 fn test_example1_timeout() {
     let res = test_send_and_reply_raw(
@@ -174,68 +290,210 @@ fn test_example1_timeout() {
 
 #[test]
 fn test_master_retry_lock() {
-    let mut transmitter = TestTransmitter { sent: vec![] };
+    let mut transmitter = BusTransmitter::default();
     let msg = example1();
 
     let mut driver = EbusDriver::new(Duration::from_micros(123), 0x9B, 0x5C, 0);
     for _ in 0..50 {
         driver
-            .process(0xAA, &mut transmitter, sleep, None, true)
+            .process(0xAA, &mut transmitter, &NoClock, None, true)
             .unwrap();
     }
     driver
-        .process(0xAA, &mut transmitter, sleep, Some(&msg), true)
+        .process(0xAA, &mut transmitter, &NoClock, Some(&msg), true)
         .unwrap();
     let res = driver
-        .process(0x03, &mut transmitter, sleep, Some(&msg), true)
+        .process(0x03, &mut transmitter, &NoClock, Some(&msg), true)
         .unwrap();
 
     assert!(matches!(res, ProcessResult::None));
 
     transmitter.sent.clear();
     driver
-        .process(0xAA, &mut transmitter, sleep, Some(&msg), true)
+        .process(0xAA, &mut transmitter, &NoClock, Some(&msg), true)
         .unwrap();
 
     assert!(transmitter.sent.is_empty());
 
     driver
-        .process(0xAA, &mut transmitter, sleep, Some(&msg), true)
+        .process(0xAA, &mut transmitter, &NoClock, Some(&msg), true)
         .unwrap();
 
     assert!(transmitter.sent.is_empty());
 
     driver
-        .process(0xAA, &mut transmitter, sleep, Some(&msg), true)
+        .process(0xAA, &mut transmitter, &NoClock, Some(&msg), true)
         .unwrap();
     assert_eq!(*transmitter.sent.last().unwrap(), msg.telegram.src);
+
+    let stats = driver.stats();
+    assert_eq!(stats.arbitration_lost, 1);
+    assert_eq!(stats.fairness_waits, 2);
 }
 
 #[test]
 fn interrupt_lock() {
-    let mut transmitter = TestTransmitter { sent: vec![] };
+    let mut transmitter = BusTransmitter::default();
     let msg = example1();
 
     let mut driver = EbusDriver::new(Duration::from_micros(123), 0x9B, 0x5C, 0);
     for _ in 0..50 {
         driver
-            .process(0xAA, &mut transmitter, sleep, None, true)
+            .process(0xAA, &mut transmitter, &NoClock, None, true)
             .unwrap();
     }
     driver
-        .process(0xAA, &mut transmitter, sleep, Some(&msg), true)
+        .process(0xAA, &mut transmitter, &NoClock, Some(&msg), true)
         .unwrap();
     driver
-        .process(msg.telegram.src, &mut transmitter, sleep, Some(&msg), true)
+        .process(
+            msg.telegram.src,
+            &mut transmitter,
+            &NoClock,
+            Some(&msg),
+            true,
+        )
         .unwrap();
     driver
-        .process(0xFF, &mut transmitter, sleep, Some(&msg), true)
+        .process(0xFF, &mut transmitter, &NoClock, Some(&msg), true)
         .unwrap();
 
     let len = transmitter.sent.len();
     driver
-        .process(0xAA, &mut transmitter, sleep, Some(&msg), true)
+        .process(0xAA, &mut transmitter, &NoClock, Some(&msg), true)
         .unwrap();
 
     assert_eq!(transmitter.sent.len(), len);
 }
+
+/// Lets the driver lock the bus and send `msg`, processing its own echo
+fn send_with_clock(
+    driver: &mut EbusDriver,
+    transmitter: &mut BusTransmitter,
+    clock: &ByteClock,
+    msg: &MasterTelegram,
+) {
+    driver
+        .process(0xAA, transmitter, clock, Some(msg), true)
+        .unwrap();
+    while !transmitter.sent.is_empty() {
+        clock.advance(Duration::from_millis(4));
+        let word = transmitter.sent.remove(0);
+        driver
+            .process(word, transmitter, clock, Some(msg), true)
+            .unwrap();
+    }
+}
+
+#[test]
+fn test_vet_by_poll_and_latency() {
+    let mut transmitter = BusTransmitter::default();
+    let clock = ByteClock::default();
+    let mut driver = EbusDriver::new(Duration::from_micros(123), 0x9B, 0x5C, 0);
+    let msg = MasterTelegram {
+        telegram: Telegram {
+            src: 0x10,
+            dest: 0x15,
+            service: 0x0700,
+            data: Buffer::from_slice(&[]),
+        },
+        flags: TelegramFlag::ExpectReply | TelegramFlags::none(),
+    };
+
+    send_with_clock(&mut driver, &mut transmitter, &clock, &msg);
+    let crc = Crc::new(0x9B).add_multiple(&[0x01, 0x42]).calc_crc();
+    let mut res = ProcessResult::None;
+    for word in [0x00, 0x01, 0x42, crc] {
+        clock.advance(Duration::from_millis(4));
+        res = driver
+            .process(word, &mut transmitter, &clock, Some(&msg), true)
+            .unwrap();
+    }
+    assert!(matches!(res, ProcessResult::VetReply { .. }));
+
+    // too early
+    clock.advance(Duration::from_millis(3));
+    driver.poll(&mut transmitter, &clock).unwrap();
+    assert!(transmitter.sent.is_empty());
+
+    clock.advance(Duration::from_millis(4));
+    driver.poll(&mut transmitter, &clock).unwrap();
+    assert_eq!(transmitter.sent, [0x00]);
+
+    let res = driver
+        .process(0x00, &mut transmitter, &clock, Some(&msg), true)
+        .unwrap();
+    assert_eq!(res.as_reply(), Some(&[0x42][..]));
+    // 6 bytes telegram, 4 bytes reply, 7 ms vetting
+    assert_eq!(driver.last_latency(), Some(Duration::from_millis(47)));
+}
+
+#[test]
+fn test_gap_is_timeout() {
+    let mut transmitter = BusTransmitter::default();
+    let clock = ByteClock::default();
+    let mut driver = EbusDriver::new(Duration::from_micros(123), 0x9B, 0x5C, 0);
+    let msg = MasterTelegram {
+        telegram: Telegram {
+            src: 0x10,
+            dest: 0x15,
+            service: 0x0700,
+            data: Buffer::from_slice(&[]),
+        },
+        flags: TelegramFlags::none(),
+    };
+
+    send_with_clock(&mut driver, &mut transmitter, &clock, &msg);
+    // the ACK arrives after AUTO-SYN should have come
+    clock.advance(Duration::from_millis(100));
+    let res = driver
+        .process(0x00, &mut transmitter, &clock, Some(&msg), true)
+        .unwrap();
+    assert_eq!(res, ProcessResult::Timeout);
+}
+
+#[test]
+fn test_stats() {
+    let mut d = AutoLoopback::new();
+    // letting SYNs pass while the fairness counter runs down
+    assert_eq!(d.stats().fairness_waits, 8);
+    d.reset_stats();
+
+    let msg = example1();
+    d.process(0xAA, Some(&msg));
+    d.process(0xFF, Some(&msg));
+    d.process_multiple(&[0x00, 0x02, 0xA9, 0x00, 0xDA, 0x83], Some(&msg));
+    // invalid escape sequence in the repeated reply
+    d.process_multiple(&[0x02, 0xA9, 0x05], Some(&msg));
+
+    let stats = d.stats();
+    assert_eq!(stats.arbitration_won, 1);
+    assert_eq!(stats.telegrams_sent, 2);
+    assert_eq!(stats.nacks, 1);
+    assert_eq!(stats.reply_crc_errors, 1);
+    assert_eq!(stats.invalid_escape, 1);
+    assert_eq!(stats.telegrams_seen, 0);
+}
+
+#[test]
+fn test_events() {
+    let mut d = AutoLoopback::new();
+    let msg = example1();
+
+    d.process(0xAA, Some(&msg));
+    // not an ACK, then a reply with more than MAX_BUF bytes
+    d.process(0x55, Some(&msg));
+    d.process_multiple(&[0x00, 0x40, 0x01, 0x02], Some(&msg));
+    // invalid escape sequence
+    d.process_multiple(&[0xAA, 0x10, 0xA9, 0x05], None);
+
+    assert_eq!(
+        d.drain_events(),
+        [
+            BusEvent::TelegramNotAcked { byte: 0x55 },
+            BusEvent::ReplyLenOverflow { len: 0x40 },
+            BusEvent::InvalidEscape { byte: 0x05 },
+        ]
+    );
+    assert!(d.drain_events().is_empty());
+}
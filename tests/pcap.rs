@@ -0,0 +1,151 @@
+#![cfg(feature = "std")]
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use ebus::{
+    pcap::{Direction, PcapWriter, LINKTYPE_SYMBOLS, LINKTYPE_TELEGRAM},
+    Buffer, ProcessResult, Telegram, Transaction,
+};
+
+/// Block type and body of every block
+fn blocks(mut capture: &[u8]) -> Vec<(u32, &[u8])> {
+    let u32_at =
+        |bytes: &[u8], at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+
+    let mut blocks = vec![];
+    while !capture.is_empty() {
+        let kind = u32_at(capture, 0);
+        let len = u32_at(capture, 4) as usize;
+        assert_eq!(len % 4, 0);
+        assert_eq!(u32_at(capture, len - 4) as usize, len);
+        blocks.push((kind, &capture[8..len - 4]));
+        capture = &capture[len..];
+    }
+
+    blocks
+}
+
+/// Value of the first option `code` after `offset`
+fn option(body: &[u8], mut offset: usize, code: u16) -> Option<&[u8]> {
+    loop {
+        let opt = u16::from_le_bytes([body[offset], body[offset + 1]]);
+        let len = u16::from_le_bytes([body[offset + 2], body[offset + 3]]) as usize;
+        if opt == 0 {
+            return None;
+        }
+        if opt == code {
+            return Some(&body[offset + 4..offset + 4 + len]);
+        }
+        offset += 4 + len.next_multiple_of(4);
+    }
+}
+
+/// Packet data and comment of an enhanced packet block
+fn packet(body: &[u8]) -> (u32, &[u8], Option<&str>) {
+    let interface = u32::from_le_bytes(body[0..4].try_into().unwrap());
+    let len = u32::from_le_bytes(body[12..16].try_into().unwrap()) as usize;
+    let comment = option(body, 20 + len.next_multiple_of(4), 1)
+        .map(|comment| std::str::from_utf8(comment).unwrap());
+
+    (interface, &body[20..20 + len], comment)
+}
+
+fn telegram() -> Telegram {
+    Telegram {
+        src: 0x10,
+        dest: 0x08,
+        service: 0xB509,
+        data: Buffer::from_slice(&[0x0D, 0x06, 0x00]),
+    }
+}
+
+#[test]
+fn transactions() {
+    let time = UNIX_EPOCH + Duration::from_micros(0x1_0000_0002);
+    let mut writer = PcapWriter::new(vec![]).unwrap();
+    writer
+        .write_transaction(
+            time,
+            &Transaction {
+                telegram: telegram(),
+                telegram_crc_ok: true,
+                slave_ack: Some(0x00),
+                reply: Some(Buffer::from_slice(&[0x42, 0x43])),
+                reply_crc_ok: Some(false),
+                master_ack: Some(0xFF),
+            },
+        )
+        .unwrap();
+    let broadcast = ProcessResult::Broadcast {
+        telegram: Telegram {
+            dest: 0xFE,
+            ..telegram()
+        },
+    };
+    assert!(writer.write_result(time, &broadcast).unwrap());
+    assert!(!writer.write_result(time, &ProcessResult::Timeout).unwrap());
+    writer
+        .write_symbols(time, &[0xAA, 0x10], Direction::Outbound)
+        .unwrap();
+
+    let capture = writer.into_inner();
+    let blocks = blocks(&capture);
+    let kinds: Vec<_> = blocks.iter().map(|(kind, _)| *kind).collect();
+    assert_eq!(kinds, [0x0A0D0D0A, 1, 1, 6, 6, 6]);
+    assert_eq!(&blocks[0].1[..4], &0x1A2B3C4Du32.to_le_bytes());
+    assert_eq!(&blocks[1].1[..2], &LINKTYPE_TELEGRAM.to_le_bytes());
+    assert_eq!(&blocks[2].1[..2], &LINKTYPE_SYMBOLS.to_le_bytes());
+
+    // timestamp in microseconds, high word first
+    assert_eq!(&blocks[3].1[4..12], &[1, 0, 0, 0, 2, 0, 0, 0]);
+    let (interface, data, comment) = packet(blocks[3].1);
+    assert_eq!(interface, 0);
+    assert_eq!(
+        data,
+        [0x17, 0x10, 0x08, 0xB5, 0x09, 0x03, 0x0D, 0x06, 0x00, 0x00, 0x02, 0x42, 0x43, 0xFF]
+            .as_slice()
+    );
+    assert_eq!(
+        comment,
+        Some("telegram CRC ok, ACK, reply CRC error, master NACK")
+    );
+
+    let (_, data, comment) = packet(blocks[4].1);
+    assert_eq!(
+        data,
+        [0x01, 0x10, 0xFE, 0xB5, 0x09, 0x03, 0x0D, 0x06, 0x00].as_slice()
+    );
+    assert_eq!(comment, Some("broadcast, telegram CRC ok"));
+
+    let (interface, data, comment) = packet(blocks[5].1);
+    assert_eq!(
+        (interface, data, comment),
+        (1, [0xAA, 0x10].as_slice(), None)
+    );
+    let flags = option(blocks[5].1, 20 + 4, 2).unwrap();
+    assert_eq!(flags, 2u32.to_le_bytes());
+}
+
+#[test]
+fn missing_ack() {
+    let mut writer = PcapWriter::new(vec![]).unwrap();
+    writer
+        .write_transaction(
+            SystemTime::now(),
+            &Transaction {
+                telegram: telegram(),
+                telegram_crc_ok: false,
+                slave_ack: None,
+                reply: None,
+                reply_crc_ok: None,
+                master_ack: None,
+            },
+        )
+        .unwrap();
+
+    let capture = writer.into_inner();
+    let (_, data, comment) = packet(blocks(&capture)[3].1);
+    assert_eq!(data[0], 0x00);
+    assert_eq!(data.len(), 9);
+    assert_eq!(comment, Some("telegram CRC error, no ACK"));
+}
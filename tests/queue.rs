@@ -0,0 +1,256 @@
+mod helper;
+
+use std::{collections::VecDeque, task::Poll, time::Duration};
+
+use ebus::{
+    queue::{RetryPolicy, TxError, TxQueue},
+    Buffer, EbusDriver, MasterTelegram, Outcome, ProcessResult, SendError, Telegram, TelegramFlag,
+    TelegramFlags,
+};
+use helper::{BusTransmitter, ByteClock};
+
+fn driver(addr: u8) -> EbusDriver {
+    let mut driver = EbusDriver::new(Duration::from_micros(123), 0x9B, 0x5C, 0);
+    driver.set_master_address(addr).unwrap();
+
+    driver
+}
+
+fn msg(src: u8, dest: u8) -> MasterTelegram {
+    MasterTelegram {
+        telegram: Telegram {
+            src,
+            dest,
+            service: 0x0705,
+            data: Buffer::from_slice(&[0x01]),
+        },
+        flags: TelegramFlags::none(),
+    }
+}
+
+/// Master 0x10 sending from the queue, master 0x30 acknowledging telegrams to it and
+/// answering requests to its slave address 0x35 with `DEAD`.
+///
+/// Runs until the queue is empty, calling `step` with the result of the sender after every
+/// byte; one byte takes 1 ms. Replies are acknowledged after `step`.
+/// Returns all results of the sender.
+fn run<const N: usize>(
+    queue: &mut TxQueue<N>,
+    mut step: impl FnMut(&mut TxQueue<N>, &ProcessResult),
+) -> Vec<ProcessResult> {
+    let mut sender = driver(0x10);
+    let mut sender_tx = BusTransmitter::default();
+    let mut other = driver(0x30);
+    let mut other_tx = BusTransmitter::default();
+
+    let clock = ByteClock::default();
+    let mut bus = VecDeque::new();
+    let mut results = Vec::new();
+    for _ in 0..10_000 {
+        if queue.is_empty() {
+            break;
+        }
+
+        bus.extend(sender_tx.sent.drain(..));
+        bus.extend(other_tx.sent.drain(..));
+        // AUTO-SYN
+        let word = bus.pop_front().unwrap_or(0xAA);
+
+        clock.advance(Duration::from_millis(1));
+        let res = queue
+            .process(&mut sender, word, &mut sender_tx, &clock, true)
+            .unwrap();
+        let other_res = other
+            .process(word, &mut other_tx, &clock, None, true)
+            .unwrap();
+        if let ProcessResult::Request { token, .. } = other_res {
+            other
+                .reply_as_slave(&[0xDE, 0xAD], &mut other_tx, token)
+                .unwrap();
+        }
+
+        step(queue, &res);
+        if let ProcessResult::VetReply { .. } = res {
+            sender.vet_timeout(&mut sender_tx).unwrap();
+        }
+        if !res.is_none() {
+            results.push(res);
+        }
+    }
+
+    assert!(queue.is_empty());
+    results
+}
+
+#[test]
+fn priority_order() {
+    let mut queue = TxQueue::<4>::new();
+    // priority class 2, then priority class 0
+    let low = queue
+        .enqueue(msg(0x13, 0x30), None, Default::default())
+        .unwrap();
+    let high = queue
+        .enqueue(msg(0x10, 0x30), None, Default::default())
+        .unwrap();
+    let high2 = queue
+        .enqueue(msg(0x10, 0x30), None, Default::default())
+        .unwrap();
+
+    let mut done = Vec::new();
+    run(&mut queue, |queue, _| {
+        for (name, handle) in [("low", &low), ("high", &high), ("high2", &high2)] {
+            if !done.contains(&name) && queue.poll(handle).is_ready() {
+                done.push(name);
+            }
+        }
+    });
+
+    assert_eq!(done, ["high", "high2", "low"]);
+}
+
+#[test]
+fn retry_on_timeout() {
+    let mut queue = TxQueue::<2>::new();
+    let mut request = msg(0x10, 0x15);
+    request.flags = TelegramFlag::ExpectReply | TelegramFlags::none();
+    let retry = RetryPolicy {
+        timeout: 1,
+        reply_crc_error: 0,
+    };
+    let handle = queue.enqueue(request, None, retry).unwrap();
+
+    // nobody answers at 0x15
+    let results = run(&mut queue, |_, _| {});
+    let timeouts = results
+        .iter()
+        .filter(|r| matches!(r, ProcessResult::Timeout))
+        .count();
+    assert_eq!(timeouts, 2);
+    assert_eq!(
+        queue.poll(&handle),
+        Poll::Ready(Err(TxError::Send(SendError::Timeout)))
+    );
+    assert_eq!(queue.poll(&handle), Poll::Ready(Err(TxError::Gone)));
+}
+
+#[test]
+fn acked_and_expired() {
+    let mut queue = TxQueue::<2>::new();
+    let acked = queue
+        .enqueue(msg(0x10, 0x30), None, Default::default())
+        .unwrap();
+    // can't be sent before the first one is done
+    let expired = queue
+        .enqueue(
+            msg(0x10, 0x30),
+            Some(Duration::from_millis(5)),
+            Default::default(),
+        )
+        .unwrap();
+    assert!(queue
+        .enqueue(msg(0x10, 0x30), None, Default::default())
+        .is_err());
+
+    run(&mut queue, |_, _| {});
+    assert_eq!(
+        queue.poll(&acked),
+        Poll::Ready(Ok(Outcome::Acked { attempts: 1 }))
+    );
+    assert_eq!(queue.poll(&expired), Poll::Ready(Err(TxError::Expired)));
+}
+
+#[test]
+fn detach_frees_slot() {
+    let mut queue = TxQueue::<1>::new();
+    let handle = queue
+        .enqueue(msg(0x10, 0x30), None, Default::default())
+        .unwrap();
+    queue.detach(handle);
+
+    run(&mut queue, |_, _| {});
+    assert!(queue
+        .enqueue(msg(0x10, 0x30), None, Default::default())
+        .is_ok());
+}
+
+#[test]
+fn enqueue_while_vetting_reply() {
+    let mut queue = TxQueue::<2>::new();
+    // priority class 2
+    let mut request = msg(0x13, 0x35);
+    request.flags = TelegramFlag::ExpectReply | TelegramFlags::none();
+    let request = queue.enqueue(request, None, Default::default()).unwrap();
+
+    let mut urgent = None;
+    let results = run(&mut queue, |queue, res| {
+        if matches!(res, ProcessResult::VetReply { .. }) && urgent.is_none() {
+            // priority class 0, must not take over the reply of the request
+            urgent = Some(
+                queue
+                    .enqueue(msg(0x10, 0x30), None, Default::default())
+                    .unwrap(),
+            );
+        }
+    });
+
+    assert_eq!(
+        queue.poll(&request),
+        Poll::Ready(Ok(Outcome::Reply {
+            data: Buffer::from_slice(&[0xDE, 0xAD]),
+            attempts: 1
+        }))
+    );
+    assert_eq!(
+        queue.poll(&urgent.unwrap()),
+        Poll::Ready(Ok(Outcome::Acked { attempts: 1 }))
+    );
+    let outcomes = results.iter().filter(|res| res.outcome().is_some()).count();
+    assert_eq!(outcomes, 2);
+}
+
+#[test]
+fn reply_len_overflow() {
+    let mut queue = TxQueue::<1>::new();
+    let mut request = msg(0x10, 0x35);
+    request.flags = TelegramFlag::ExpectReply | TelegramFlags::none();
+    let handle = queue.enqueue(request, None, Default::default()).unwrap();
+
+    let mut sender = driver(0x10);
+    let mut sender_tx = BusTransmitter::default();
+    let clock = ByteClock::default();
+    let mut results = Vec::new();
+    let mut process = |queue: &mut TxQueue<1>, word| {
+        clock.advance(Duration::from_millis(1));
+        let res = queue
+            .process(&mut sender, word, &mut sender_tx, &clock, true)
+            .unwrap();
+        results.push(res);
+        std::mem::take(&mut sender_tx.sent)
+    };
+
+    // arbitration and telegram come back from the bus
+    let mut bus = VecDeque::from(process(&mut queue, 0xAA));
+    while let Some(word) = bus.pop_front() {
+        bus.extend(process(&mut queue, word));
+    }
+    // the slave acknowledges and announces 255 bytes
+    process(&mut queue, 0x00);
+    process(&mut queue, 0xFF);
+    assert_eq!(
+        queue.poll(&handle),
+        Poll::Ready(Err(TxError::Send(SendError::ReplyLenOverflow)))
+    );
+
+    // not sent again
+    for _ in 0..3 {
+        assert!(process(&mut queue, 0xAA).is_empty());
+    }
+    assert!(queue.is_empty());
+    assert_eq!(
+        results
+            .iter()
+            .filter(|res| !res.is_none())
+            .collect::<Vec<_>>(),
+        [&ProcessResult::ReplyLenOverflow { len: 0xFF }]
+    );
+}
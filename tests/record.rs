@@ -0,0 +1,318 @@
+#![cfg(feature = "std")]
+
+mod helper;
+
+use std::time::Duration;
+
+use ebus::{
+    record::{replay, Event, Reader, Record, Recorder, ReplayError, Writer, MAX_RECORD},
+    Buffer, Crc, EbusDriver, MasterTelegram, ProcessResult, Telegram, TelegramFlag, TelegramFlags,
+    Transaction,
+};
+use helper::{BusTransmitter, ByteClock};
+
+type BusRecorder<'a> = Recorder<BusTransmitter, &'a ByteClock, Vec<u8>>;
+
+fn driver() -> EbusDriver {
+    let mut driver = EbusDriver::new(Duration::ZERO, 0x9B, 0x5C, 0);
+    driver.set_master_address(0x10).unwrap();
+    driver.set_observe(true);
+
+    driver
+}
+
+fn msg() -> MasterTelegram {
+    MasterTelegram {
+        telegram: Telegram {
+            src: 0x10,
+            dest: 0x35,
+            service: 0x0700,
+            data: Buffer::from_slice(&[0x01]),
+        },
+        flags: TelegramFlag::ExpectReply | TelegramFlags::none(),
+    }
+}
+
+fn with_crc(bytes: &[u8]) -> Vec<u8> {
+    let crc = Crc::new(0x9B).add_multiple(bytes).calc_crc();
+
+    [bytes, &[crc]].concat()
+}
+
+/// Process `word` and everything the driver transmits in turn, as it comes back from the
+/// bus. `msg` is offered until it is done, requests are answered with `42`.
+fn process(
+    driver: &mut EbusDriver,
+    recorder: &mut BusRecorder,
+    clock: &ByteClock,
+    word: u8,
+    msg: Option<&MasterTelegram>,
+    results: &mut Vec<ProcessResult>,
+) {
+    let mut msg = msg;
+    let mut words = vec![word];
+    while !words.is_empty() {
+        for word in std::mem::take(&mut words) {
+            clock.advance(Duration::from_micros(4167));
+            recorder.offer(msg).unwrap();
+            recorder.receive(&[word]).unwrap();
+            let res = driver.process(word, recorder, clock, msg, true).unwrap();
+            recorder.result(&res).unwrap();
+
+            match res {
+                ProcessResult::VetReply { .. } => {
+                    recorder.vet_timeout().unwrap();
+                    driver.vet_timeout(recorder).unwrap();
+                }
+                ProcessResult::Request { token, .. } => {
+                    recorder.reply(Some(&[0x42])).unwrap();
+                    driver.reply_as_slave(&[0x42], recorder, token).unwrap();
+                    continue;
+                }
+                // done with the telegram
+                _ if res.outcome().is_some() => msg = None,
+                _ => {}
+            }
+            results.push(res);
+        }
+        words = std::mem::take(&mut recorder.transmit().sent);
+    }
+}
+
+/// Send `msg` to slave 0x35 answering `42`, answer a request to our slave address and
+/// observe a telegram between two other masters
+fn record() -> (Vec<u8>, Vec<ProcessResult>) {
+    let mut driver = driver();
+    let clock = ByteClock::default();
+    let mut recorder = Recorder::new(BusTransmitter::default(), &clock, vec![]).unwrap();
+    let mut results: Vec<ProcessResult> = vec![];
+    let msg = msg();
+
+    // the slave answers once our telegram is through
+    let mut slave = with_crc(&[0x01, 0x42]);
+    slave.insert(0, 0x00);
+    let mut slave = slave.into_iter();
+    let mut word = 0xAA;
+    while !results.iter().any(|res| res.outcome().is_some()) {
+        process(
+            &mut driver,
+            &mut recorder,
+            &clock,
+            word,
+            Some(&msg),
+            &mut results,
+        );
+        word = driver
+            .is_sending()
+            .then(|| slave.next())
+            .flatten()
+            .unwrap_or(0xAA);
+    }
+
+    let request = with_crc(&[0x30, 0x15, 0x07, 0x00, 0x01, 0x05]);
+    let observed = with_crc(&[0x03, 0x07, 0x07, 0x04, 0x00]);
+    let bus = [&[0xAA][..], &request]
+        .into_iter()
+        .flatten()
+        // the master acknowledges our reply
+        .chain(&[0x00, 0xAA])
+        .chain(&observed)
+        .chain(&[0x00, 0xAA]);
+    for &word in bus {
+        process(&mut driver, &mut recorder, &clock, word, None, &mut results);
+    }
+
+    (recorder.into_inner().1, results)
+}
+
+#[test]
+fn replay_recording() {
+    let (recording, results) = record();
+    let results: Vec<_> = results.into_iter().filter(|res| !res.is_none()).collect();
+    assert!(matches!(
+        &results[..],
+        [
+            ProcessResult::VetReply { .. },
+            ProcessResult::Reply { .. },
+            ProcessResult::SlaveAckOk { attempts: 1 },
+            ProcessResult::Observed { .. },
+        ]
+    ));
+    assert_eq!(results[1].as_reply(), Some(&[0x42][..]));
+
+    let records: Vec<_> = Reader::new(&recording[..])
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(records[0].event, Event::Msg(Some(msg())));
+    assert_eq!(
+        records[2].event,
+        Event::Tx(vec![0x10]),
+        "arbitration after the first SYN"
+    );
+    assert!(records
+        .iter()
+        .any(|record| record.event == Event::Reply(Some(Buffer::from_slice(&[0x42])))));
+    assert!(records.windows(2).all(|w| w[0].at <= w[1].at));
+
+    let replayed = replay(&mut driver(), Reader::new(&recording[..]).unwrap()).unwrap();
+    let request = replayed.results.iter().position(|res| match res {
+        Event::Request(telegram) => telegram.dest == 0x15,
+        _ => false,
+    });
+    let mut replayed = replayed.results;
+    replayed.remove(request.expect("the request"));
+    let results: Vec<_> = results.into_iter().map(Event::Result).collect();
+    assert_eq!(replayed, results);
+}
+
+#[test]
+fn replay_bytes_only() {
+    let (recording, _) = record();
+    let mut bytes = Writer::new(vec![]).unwrap();
+    for record in Reader::new(&recording[..]).unwrap() {
+        let record = record.unwrap();
+        if matches!(record.event, Event::Rx { .. } | Event::Tx(_)) {
+            bytes.write(&record).unwrap();
+        }
+    }
+    let bytes = bytes.into_inner();
+
+    // without the offered telegram our own transaction is someone else's
+    let replayed = replay(&mut driver(), Reader::new(&bytes[..]).unwrap());
+    let Err(ReplayError::Mismatch(mismatch)) = replayed else {
+        panic!("replay without the offered telegram succeeded");
+    };
+    assert_eq!(mismatch.expected, Some(Event::Tx(vec![0x10])));
+    assert_eq!(mismatch.found, Some(Event::Tx(vec![])));
+}
+
+#[test]
+fn replay_mismatch() {
+    let (recording, _) = record();
+
+    // waits for more SYNs before arbitrating
+    let mut other = EbusDriver::new(Duration::ZERO, 0x9B, 0x5C, 8);
+    other.set_master_address(0x10).unwrap();
+    other.set_observe(true);
+    let Err(ReplayError::Mismatch(mismatch)) =
+        replay(&mut other, Reader::new(&recording[..]).unwrap())
+    else {
+        panic!("replay with a different fairness succeeded");
+    };
+    assert_eq!(mismatch.expected, Some(Event::Tx(vec![0x10])));
+
+    // does not report the observed telegram
+    let mut other = driver();
+    other.set_observe(false);
+    let replayed = replay(&mut other, Reader::new(&recording[..]).unwrap());
+    let Err(ReplayError::Mismatch(mismatch)) = replayed else {
+        panic!("replay without observing succeeded");
+    };
+    assert!(matches!(
+        mismatch.expected,
+        Some(Event::Result(ProcessResult::Observed { .. }))
+    ));
+}
+
+#[test]
+fn long_reads() {
+    let clock = ByteClock::default();
+    let mut recorder = Recorder::new(BusTransmitter::default(), &clock, vec![]).unwrap();
+    recorder.receive(&[0xAA; MAX_RECORD + 1]).unwrap();
+
+    let recording = recorder.into_inner().1;
+    let records: Vec<_> = Reader::new(&recording[..])
+        .unwrap()
+        .map(|record| record.unwrap().event)
+        .collect();
+    assert_eq!(
+        records,
+        [
+            Event::Rx {
+                bytes: vec![0xAA; MAX_RECORD],
+                is_low_latency: false,
+            },
+            Event::Rx {
+                bytes: vec![0xAA],
+                is_low_latency: true,
+            },
+        ]
+    );
+
+    let mut writer = Writer::new(vec![]).unwrap();
+    let too_long = Record {
+        at: Duration::ZERO,
+        event: Event::Tx(vec![0xAA; MAX_RECORD + 1]),
+    };
+    assert!(writer.write(&too_long).is_err());
+}
+
+#[test]
+fn format_roundtrip() {
+    let telegram = msg().telegram;
+    let events = vec![
+        Event::Rx {
+            bytes: vec![0xAA],
+            is_low_latency: false,
+        },
+        Event::Rx {
+            bytes: vec![],
+            is_low_latency: true,
+        },
+        Event::Tx(vec![0xA9; 200]),
+        Event::Msg(None),
+        Event::Msg(Some(MasterTelegram {
+            telegram: telegram.clone(),
+            flags: TelegramFlag::NeedsDataCrc | TelegramFlag::ExpectReply,
+        })),
+        Event::Reply(None),
+        Event::Reply(Some(Buffer::from_slice(&[1, 2, 3]))),
+        Event::VetTimeout,
+        Event::Request(telegram.clone()),
+        Event::Result(ProcessResult::VetReply { timeout_ms: 300 }),
+        Event::Result(ProcessResult::ReplyLenOverflow { len: 0xFF }),
+        Event::Result(ProcessResult::Reply {
+            data: Buffer::from_slice(&[0x42]),
+            clean: true,
+            attempts: 2,
+        }),
+        Event::Result(ProcessResult::Transaction {
+            transaction: Transaction {
+                telegram,
+                telegram_crc_ok: true,
+                slave_ack: Some(0x00),
+                reply: Some(Buffer::from_slice(&[0x42])),
+                reply_crc_ok: Some(false),
+                master_ack: None,
+            },
+        }),
+    ];
+    let records: Vec<_> = events
+        .into_iter()
+        .enumerate()
+        .map(|(i, event)| Record {
+            at: Duration::from_millis(i as u64 * 500),
+            event,
+        })
+        .collect();
+
+    let mut writer = Writer::new(vec![]).unwrap();
+    for record in &records {
+        writer.write(record).unwrap();
+    }
+    let recording = writer.into_inner();
+    assert_eq!(&recording[..8], b"EBUSREC\x01");
+
+    let read: Vec<_> = Reader::new(&recording[..])
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(read, records);
+
+    assert!(Reader::new(&b"EBUSREC\x02"[..]).is_err());
+    let truncated = Reader::new(&recording[..recording.len() - 1])
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>();
+    assert!(truncated.is_err());
+}
@@ -0,0 +1,70 @@
+mod helper;
+
+use std::{collections::VecDeque, time::Duration};
+
+use ebus::{
+    scan::Scanner,
+    service::{Identification, Version},
+    Address, EbusDriver, ProcessResult,
+};
+use helper::{BusTransmitter, NoClock};
+
+fn driver() -> EbusDriver {
+    EbusDriver::new(Duration::from_micros(123), 0x9B, 0x5C, 0)
+}
+
+#[test]
+fn scan_loopback() {
+    let id = Identification {
+        manufacturer: 0x50,
+        device_id: *b"HEAT1",
+        sw_version: Version { major: 1, minor: 0 },
+        hw_version: Version { major: 2, minor: 0 },
+    };
+
+    // a second participant answering identification requests to 0x08
+    let mut slave = driver();
+    slave.set_master_address(0x03).unwrap();
+    slave.set_identification(Some(&id)).unwrap();
+    let mut slave_tx = BusTransmitter::default();
+
+    let mut master = driver();
+    master.set_master_address(0x10).unwrap();
+    let mut master_tx = BusTransmitter::default();
+    let mut scanner = Scanner::new(0x10);
+
+    let mut bus = VecDeque::new();
+    let mut vetting = false;
+    for _ in 0..100_000 {
+        if scanner.is_done() {
+            break;
+        }
+
+        bus.extend(master_tx.sent.drain(..));
+        bus.extend(slave_tx.sent.drain(..));
+
+        if bus.is_empty() {
+            if vetting {
+                master.vet_timeout(&mut master_tx).unwrap();
+                bus.extend(master_tx.sent.drain(..));
+                vetting = false;
+            } else {
+                // AUTO-SYN
+                bus.push_back(0xAA);
+            }
+        }
+        let word = bus.pop_front().unwrap();
+
+        let res = scanner
+            .process(&mut master, word, &mut master_tx, &NoClock, true)
+            .unwrap();
+        vetting |= matches!(res, ProcessResult::VetReply { .. });
+        slave
+            .process(word, &mut slave_tx, &NoClock, None, true)
+            .unwrap();
+    }
+
+    assert!(scanner.is_done());
+    let devices: Vec<_> = scanner.devices().collect();
+    assert_eq!(devices, [(Address(0x08), &id)]);
+}
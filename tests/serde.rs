@@ -0,0 +1,130 @@
+#![cfg(feature = "serde")]
+
+mod helper;
+
+use std::time::Duration;
+
+use ebus::{
+    Buffer, BusEvent, MasterTelegram, Outcome, ProcessResult, SendError, Stats, Telegram,
+    TelegramFlag, TelegramFlags, Transaction,
+};
+use helper::{example1, AutoLoopback};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::json;
+
+fn telegram() -> Telegram {
+    Telegram {
+        src: 0x10,
+        dest: 0x08,
+        service: 0xB509,
+        data: Buffer::from_slice(&[0x0D, 0xA9, 0xAA]),
+    }
+}
+
+fn round_trip<T: Serialize + DeserializeOwned + PartialEq + std::fmt::Debug>(value: &T) {
+    let json = serde_json::to_string(value).unwrap();
+    assert_eq!(&serde_json::from_str::<T>(&json).unwrap(), value, "{json}");
+}
+
+#[test]
+fn buffer_as_hex() {
+    let buf = Buffer::from_slice(&[0x0D, 0xA9, 0xAA]);
+    assert_eq!(serde_json::to_value(&buf).unwrap(), json!("0da9aa"));
+    assert_eq!(
+        serde_json::from_value::<Buffer>(json!("0DA9AA")).unwrap(),
+        buf
+    );
+    assert_eq!(
+        serde_json::from_value::<Buffer>(json!([13, 169, 170])).unwrap(),
+        buf
+    );
+    round_trip(&Buffer::from_slice(&[]));
+    round_trip(&Buffer::from_slice(&[0xFF; 32]));
+
+    assert!(serde_json::from_value::<Buffer>(json!("0da")).is_err());
+    assert!(serde_json::from_value::<Buffer>(json!("+f")).is_err());
+    assert!(serde_json::from_value::<Buffer>(json!("00".repeat(33))).is_err());
+    assert!(serde_json::from_value::<Buffer>(json!(vec![0; 33])).is_err());
+}
+
+#[test]
+fn flags_as_names() {
+    let flags = TelegramFlag::NeedsDataCrc | TelegramFlag::ExpectReply;
+    assert_eq!(
+        serde_json::to_value(flags).unwrap(),
+        json!(["NeedsDataCrc", "ExpectReply"])
+    );
+    round_trip(&flags);
+    round_trip(&TelegramFlags::none());
+    round_trip(&(TelegramFlag::ExpectReply | TelegramFlags::none()));
+}
+
+#[test]
+fn telegrams() {
+    let msg = MasterTelegram {
+        telegram: telegram(),
+        flags: TelegramFlag::ExpectReply | TelegramFlags::none(),
+    };
+    assert_eq!(
+        serde_json::to_value(&msg).unwrap(),
+        json!({
+            "telegram": {"src": 16, "dest": 8, "service": 0xB509, "data": "0da9aa"},
+            "flags": ["ExpectReply"],
+        })
+    );
+    let json = serde_json::to_string(&msg).unwrap();
+    let back: MasterTelegram = serde_json::from_str(&json).unwrap();
+    assert_eq!(back.telegram, msg.telegram);
+    assert_eq!(back.flags, msg.flags);
+
+    round_trip(&Transaction {
+        telegram: telegram(),
+        telegram_crc_ok: true,
+        slave_ack: Some(0x00),
+        reply: Some(Buffer::from_slice(&[0x01])),
+        reply_crc_ok: Some(false),
+        master_ack: Some(0xFF),
+    });
+}
+
+#[test]
+fn results() {
+    round_trip(&Outcome::Reply {
+        data: Buffer::from_slice(&[1, 2]),
+        attempts: 1,
+    });
+    round_trip(&SendError::Timeout);
+    round_trip(&BusEvent::Gap {
+        gap: Duration::from_millis(60),
+    });
+    round_trip(&Stats {
+        nacks: 3,
+        ..Stats::default()
+    });
+
+    // results are only serialized, deserializing would allow forging request tokens
+    assert_eq!(
+        serde_json::to_value(ProcessResult::MasterAckErr { attempts: 2 }).unwrap(),
+        json!({"MasterAckErr": {"attempts": 2}})
+    );
+    assert_eq!(
+        serde_json::to_value(ProcessResult::Broadcast {
+            telegram: telegram()
+        })
+        .unwrap(),
+        json!({"Broadcast": {"telegram": serde_json::to_value(telegram()).unwrap()}})
+    );
+
+    // the token is not part of the serialized request
+    let mut d = AutoLoopback::new();
+    let msg = MasterTelegram {
+        flags: TelegramFlags::none(),
+        ..example1()
+    };
+    d.send_external_msg(&msg);
+    let request = d.process_bus(None).pop().unwrap();
+    assert_eq!(
+        serde_json::to_value(&request).unwrap(),
+        json!({"Request": {"telegram": serde_json::to_value(&msg.telegram).unwrap()}})
+    );
+}
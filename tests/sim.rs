@@ -0,0 +1,207 @@
+#![cfg(feature = "sim")]
+
+use std::{task::Poll, time::Duration};
+
+use ebus::{
+    queue::TxError, sim::SimBus, Buffer, EbusDriver, MasterTelegram, Outcome, ProcessResult,
+    SendError, Telegram, TelegramFlag, TelegramFlags,
+};
+
+fn driver(addr: u8, fairness_max: u8) -> EbusDriver {
+    let mut driver = EbusDriver::new(Duration::ZERO, 0x9B, 0x5C, fairness_max);
+    driver.set_master_address(addr).unwrap();
+
+    driver
+}
+
+fn request(src: u8, dest: u8) -> MasterTelegram {
+    MasterTelegram {
+        telegram: Telegram {
+            src,
+            dest,
+            service: 0x0700,
+            data: Buffer::from_slice(&[src]),
+        },
+        flags: TelegramFlag::ExpectReply | TelegramFlags::none(),
+    }
+}
+
+/// Source addresses of the telegrams on the bus, in order
+fn senders(bus: &SimBus) -> Vec<u8> {
+    // a telegram starts with a byte other than SYN right after a SYN
+    bus.log()
+        .windows(2)
+        .filter(|w| w[0] == 0xAA && w[1] != 0xAA)
+        .map(|w| w[1])
+        .collect()
+}
+
+#[test]
+fn collision_same_priority_class() {
+    let mut bus = SimBus::new();
+    // sub address 0 beats sub address 1 in wired-AND (0x10 & 0x30 = 0x10)
+    let a = bus.add_node(driver(0x30, 0));
+    let b = bus.add_node(driver(0x10, 0));
+    bus.add_slave(0x15, |t| Some(vec![t.data.as_bytes()[0]]));
+
+    let ha = bus
+        .queue(a)
+        .enqueue(request(0x30, 0x15), None, Default::default())
+        .unwrap();
+    let hb = bus
+        .queue(b)
+        .enqueue(request(0x10, 0x15), None, Default::default())
+        .unwrap();
+    assert!(bus.run_until(1000, |bus| bus.queue(a).is_empty()
+        && bus.queue(b).is_empty()));
+
+    assert_eq!(senders(&bus), [0x10, 0x30]);
+    let reply = |data: u8| {
+        Poll::Ready(Ok(Outcome::Reply {
+            data: Buffer::from_slice(&[data]),
+            attempts: 1,
+        }))
+    };
+    assert_eq!(bus.queue(a).poll(&ha), reply(0x30));
+    assert_eq!(bus.queue(b).poll(&hb), reply(0x10));
+}
+
+#[test]
+fn collision_priority_class() {
+    let mut bus = SimBus::new();
+    // 0x31 (class 1) & 0x70 (class 0) = 0x30, nobody wins the first round
+    let a = bus.add_node(driver(0x31, 0));
+    let b = bus.add_node(driver(0x70, 0));
+    bus.add_slave(0x15, |_| Some(vec![0x01]));
+
+    bus.queue(a)
+        .enqueue(request(0x31, 0x15), None, Default::default())
+        .unwrap();
+    bus.queue(b)
+        .enqueue(request(0x70, 0x15), None, Default::default())
+        .unwrap();
+    assert!(bus.run_until(1000, |bus| bus.queue(a).is_empty()
+        && bus.queue(b).is_empty()));
+
+    assert_eq!(senders(&bus), [0x30, 0x70, 0x31]);
+}
+
+#[test]
+fn fairness() {
+    let mut bus = SimBus::new();
+    let a = bus.add_node(driver(0x10, 3));
+    let b = bus.add_node(driver(0x30, 3));
+    bus.add_slave(0x15, |_| Some(vec![0x01]));
+
+    for _ in 0..3 {
+        bus.queue(a)
+            .enqueue(request(0x10, 0x15), None, Default::default())
+            .unwrap();
+        bus.queue(b)
+            .enqueue(request(0x30, 0x15), None, Default::default())
+            .unwrap();
+    }
+    assert!(bus.run_until(2000, |bus| bus.queue(a).is_empty()
+        && bus.queue(b).is_empty()));
+
+    // the higher priority does not starve the other master
+    assert_eq!(senders(&bus), [0x10, 0x30, 0x10, 0x30, 0x10, 0x30]);
+}
+
+#[test]
+fn latency_loses_arbitration() {
+    let mut bus = SimBus::new();
+    let a = bus.add_node(driver(0x30, 0));
+    let b = bus.add_node(driver(0x10, 0));
+    bus.set_latency(b, 1);
+    bus.add_slave(0x15, |_| Some(vec![0x01]));
+
+    bus.queue(a)
+        .enqueue(request(0x30, 0x15), None, Default::default())
+        .unwrap();
+    bus.queue(b)
+        .enqueue(request(0x10, 0x15), None, Default::default())
+        .unwrap();
+    assert!(bus.run_until(1000, |bus| bus.queue(a).is_empty()));
+
+    assert_eq!(senders(&bus)[0], 0x30);
+}
+
+#[test]
+fn missing_slave() {
+    let mut bus = SimBus::new();
+    let a = bus.add_node(driver(0x10, 0));
+
+    let handle = bus
+        .queue(a)
+        .enqueue(request(0x10, 0x15), None, Default::default())
+        .unwrap();
+    assert!(bus.run_until(1000, |bus| bus.queue(a).is_empty()));
+
+    assert_eq!(
+        bus.queue(a).poll(&handle),
+        Poll::Ready(Err(TxError::Send(SendError::Timeout)))
+    );
+    assert!(bus.results(a).contains(&ProcessResult::Timeout));
+}
+
+#[test]
+fn auto_syn_generator() {
+    let mut bus = SimBus::new();
+    bus.set_auto_syn(0);
+    let a = bus.add_node(driver(0x10, 0));
+    bus.driver(a).set_auto_syn(true);
+    bus.add_slave(0x15, |_| Some(vec![0x01]));
+
+    let handle = bus
+        .queue(a)
+        .enqueue(request(0x10, 0x15), None, Default::default())
+        .unwrap();
+    assert!(bus.run_until(1000, |bus| bus.queue(a).is_empty()));
+
+    assert!(bus.queue(a).poll(&handle).is_ready());
+    // our SYN closing the transaction is not taken for another generator
+    bus.run(5);
+    assert_eq!(senders(&bus), [0x10]);
+    assert!(bus.driver(a).is_auto_syn_active());
+}
+
+#[test]
+fn auto_syn_other_master() {
+    let mut bus = SimBus::new();
+    bus.set_auto_syn(0);
+    let a = bus.add_node(driver(0x10, 0));
+    bus.driver(a).set_auto_syn(true);
+    let b = bus.add_node(driver(0x30, 0));
+    bus.add_slave(0x15, |_| Some(vec![0x01]));
+
+    bus.queue(b)
+        .enqueue(request(0x30, 0x15), None, Default::default())
+        .unwrap();
+    assert!(bus.run_until(1000, |bus| bus.queue(b).is_empty()));
+
+    // SYN closing the telegram of another master
+    bus.run(5);
+    assert_eq!(senders(&bus), [0x30]);
+    assert!(bus.driver(a).is_auto_syn_active());
+}
+
+#[test]
+fn auto_syn_back_off() {
+    let mut bus = SimBus::new();
+    let a = bus.add_node(driver(0x10, 0));
+    bus.driver(a).set_auto_syn(true);
+
+    bus.run(100);
+    assert!(!bus.driver(a).is_auto_syn_active());
+
+    // the other generator is gone
+    bus.set_auto_syn(0);
+    bus.run(100);
+    assert!(bus.driver(a).is_auto_syn_active());
+    let syns = bus.log()[bus.log().len() - 20..]
+        .iter()
+        .filter(|&&b| b == 0xAA)
+        .count();
+    assert!(syns > 0);
+}
@@ -1,6 +1,12 @@
 mod helper;
 
-use ebus::{Buffer, MasterTelegram, ProcessResult, Telegram, TelegramFlags};
+use std::time::Duration;
+
+use ebus::{
+    service::{Identification, Version},
+    Address, Buffer, Crc, EbusDriver, InvalidAddress, MasterTelegram, ProcessResult, Telegram,
+    TelegramFlags,
+};
 use helper::{example1, AutoLoopback};
 
 #[test]
@@ -11,7 +17,7 @@ fn example1_ok() {
     d.send_external_msg(&msg);
     let mut results = d.process_bus(None);
 
-    match results.drain(..).last().unwrap() {
+    match results.pop().unwrap() {
         ProcessResult::Request { telegram, token } => {
             assert_eq!(telegram.src, 0xFF);
             d.reply_as_slave(&[0xDE, 0xAD, 0xBE, 0xEF], token)
@@ -27,12 +33,43 @@ fn example1_ok() {
 
     // ACK
     let res = d.process(0x00, None);
-    assert!(matches!(&res[..], [ProcessResult::SlaveAckOk]));
+    assert!(matches!(
+        &res[..],
+        [ProcessResult::SlaveAckOk { attempts: 1 }]
+    ));
+}
+
+#[test]
+fn repeat_reply_after_nack() {
+    let mut d = AutoLoopback::new();
+    let msg = example1();
+
+    d.send_external_msg(&msg);
+    let mut results = d.process_bus(None);
+
+    match results.pop().unwrap() {
+        ProcessResult::Request { token, .. } => d.reply_as_slave(&[0xDE, 0xAD], token),
+        other => panic!("{:?}", other),
+    }
+    d.process_bus(None);
+    let sent = d.sent_len();
+
+    // NACK, we have to send NN, data and CRC again
+    let res = d.process(0xFF, None);
+    assert!(res.iter().all(ProcessResult::is_none));
+    assert_eq!(d.sent_len(), sent + 4);
+
+    let res = d.process(0x00, None);
+    assert!(matches!(
+        &res[..],
+        [ProcessResult::SlaveAckOk { attempts: 2 }]
+    ));
 }
 
 #[test]
 fn time_program() {
     let mut d = AutoLoopback::new();
+    d.set_master_address(0xFF);
     let msg = MasterTelegram {
         telegram: Telegram {
             src: 0xFF,
@@ -45,14 +82,14 @@ fn time_program() {
 
     let mut results = d.process(0xAA, Some(&msg));
 
-    match results.drain(..).last().unwrap() {
+    match results.pop().unwrap() {
         ProcessResult::None => {}
         other => panic!("{:?}", other),
     }
 
     assert_eq!(
         d.process(0, Some(&msg)).drain(..).next().unwrap(),
-        ProcessResult::MasterAckOk
+        ProcessResult::MasterAckOk { attempts: 1 }
     );
     log::info!("processed ack");
 
@@ -68,11 +105,157 @@ fn time_program() {
         },
         flags: TelegramFlags::none(),
     });
+    let results = d.process_bus(None);
+    match results.into_iter().rfind(|res| !res.is_none()).unwrap() {
+        ProcessResult::MasterMessage { telegram } => assert_eq!(telegram.src, 0x30),
+        other => panic!("{:?}", other),
+    }
+    // ACK was sent automatically
+    assert_eq!(d.last_sent(), Some(0x00));
+}
+
+#[test]
+fn master_master_for_other_master() {
+    let mut d = AutoLoopback::new();
+    d.set_master_address(0x10);
+    let msg = MasterTelegram {
+        telegram: Telegram {
+            src: 0x30,
+            dest: 0x03,
+            service: 0x0502,
+            data: Buffer::from_slice(&[0x01]),
+        },
+        flags: TelegramFlags::none(),
+    };
+
+    d.send_external_msg(&msg);
+    let results = d.process_bus(None);
+    assert!(results.iter().all(ProcessResult::is_none));
+    assert_eq!(d.last_sent(), None);
+}
+
+#[test]
+fn master_master_without_address() {
+    let mut d = AutoLoopback::new();
+    let msg = MasterTelegram {
+        telegram: Telegram {
+            src: 0x30,
+            dest: 0x03,
+            service: 0x0502,
+            data: Buffer::from_slice(&[0x01]),
+        },
+        flags: TelegramFlags::none(),
+    };
+
+    d.send_external_msg(&msg);
     let mut results = d.process_bus(None);
-    match results.drain(..).last().unwrap() {
-        ProcessResult::Request { telegram: _, token } => {
+    match results.pop().unwrap() {
+        ProcessResult::Request { telegram, token } => {
+            assert_eq!(telegram.dest, 0x03);
             d.reply_ack(token);
         }
         other => panic!("{:?}", other),
     }
+    d.process_bus(None);
+
+    let res = d.process(0xAA, None);
+    assert!(res.iter().all(ProcessResult::is_none));
+    assert_eq!(d.last_sent(), Some(0x00));
+}
+
+#[test]
+fn broadcast() {
+    let mut d = AutoLoopback::new();
+    let msg = MasterTelegram {
+        telegram: Telegram {
+            src: 0x10,
+            dest: 0xFE,
+            service: 0x0701,
+            data: Buffer::from_slice(&[0x80, 0x0D]),
+        },
+        flags: TelegramFlags::none(),
+    };
+
+    d.send_external_msg(&msg);
+    let mut results = d.process_bus(None);
+
+    match results.pop().unwrap() {
+        ProcessResult::Broadcast { telegram } => {
+            assert_eq!(telegram.dest, 0xFE);
+            assert_eq!(telegram.data, msg.telegram.data);
+        }
+        other => panic!("{:?}", other),
+    }
+
+    // the master closes the broadcast with SYN
+    let res = d.process(0xAA, None);
+    assert!(matches!(&res[..], [ProcessResult::None]));
+}
+
+#[test]
+fn request_filtering() {
+    let mut d = AutoLoopback::new();
+    // slave address is 0x15
+    d.set_master_address(0x10);
+    d.set_observe(true);
+
+    let mut msg = example1();
+    d.send_external_msg(&msg);
+    let mut results = d.process_bus(None);
+    match results.pop().unwrap() {
+        ProcessResult::Observed { telegram } => assert_eq!(telegram.dest, 0x51),
+        other => panic!("{:?}", other),
+    }
+
+    d.send_external_bytes(&[0xAA]);
+    msg.telegram.dest = 0x15;
+    d.send_external_msg(&msg);
+    let mut results = d.process_bus(None);
+    match results.pop().unwrap() {
+        ProcessResult::Request { telegram, .. } => assert_eq!(telegram.dest, 0x15),
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn invalid_master_address() {
+    let mut driver = EbusDriver::new(Duration::ZERO, 0x9B, 0x5C, 0);
+    assert_eq!(
+        driver.set_master_address(0x15),
+        Err(InvalidAddress(Address(0x15)))
+    );
+    assert_eq!(driver.master_address(), None);
+
+    driver.set_master_address(0x10).unwrap();
+    assert!(driver.set_master_address(0xAA).is_err());
+    assert_eq!(driver.master_address(), Some(0x10));
+    assert_eq!(driver.slave_address(), Some(0x15));
+}
+
+#[test]
+fn auto_identification() {
+    let mut d = AutoLoopback::new();
+    d.set_master_address(0x10);
+    let id = Identification {
+        manufacturer: 0x50,
+        device_id: *b"EBUS1",
+        sw_version: Version { major: 1, minor: 2 },
+        hw_version: Version { major: 3, minor: 4 },
+    };
+    d.set_identification(&id);
+
+    d.send_external_msg(&Identification::request(0xFF, 0x15));
+    let results = d.process_bus(None);
+    assert!(results.iter().all(ProcessResult::is_none));
+    let mut expected = vec![
+        0x00, 0x0A, 0x50, b'E', b'B', b'U', b'S', b'1', 0x01, 0x02, 0x03, 0x04,
+    ];
+    expected.push(Crc::new(0x9B).add_multiple(&expected[1..]).calc_crc());
+    assert_eq!(d.sent(), &expected[..]);
+
+    let res = d.process(0x00, None);
+    assert!(matches!(
+        &res[..],
+        [ProcessResult::SlaveAckOk { attempts: 1 }]
+    ));
 }
@@ -0,0 +1,112 @@
+mod helper;
+
+use ebus::{Buffer, BusEvent, MasterTelegram, ProcessResult, Telegram, TelegramFlags};
+use helper::{example1, AutoLoopback};
+
+#[test]
+fn master_slave() {
+    let mut d = AutoLoopback::sniffer();
+    let msg = example1();
+
+    d.send_external_msg(&msg);
+    d.send_external_reply(&[0xA9, 0xDA]);
+    // master ACK
+    d.send_external_bytes(&[0x00]);
+    let results = d.process_bus(None);
+    assert!(results.iter().all(ProcessResult::is_none));
+
+    let mut results = d.process(0xAA, None);
+    match results.pop().unwrap() {
+        ProcessResult::Transaction { transaction } => {
+            assert_eq!(transaction.telegram.src, 0xFF);
+            assert_eq!(transaction.telegram.dest, 0x51);
+            assert!(transaction.telegram_crc_ok);
+            assert_eq!(transaction.slave_ack, Some(0x00));
+            assert_eq!(transaction.reply, Some(Buffer::from_slice(&[0xA9, 0xDA])));
+            assert_eq!(transaction.reply_crc_ok, Some(true));
+            assert_eq!(transaction.master_ack, Some(0x00));
+        }
+        other => panic!("{:?}", other),
+    }
+
+    // nothing was ever transmitted
+    assert_eq!(d.last_sent(), None);
+    assert_eq!(d.stats().telegrams_seen, 1);
+    assert_eq!(d.stats().reply_crc_errors, 0);
+}
+
+#[test]
+fn master_slave_nack() {
+    let mut d = AutoLoopback::sniffer();
+    let msg = example1();
+
+    d.send_external_msg(&msg);
+    d.send_external_bytes(&[0xFF]);
+    let mut results = d.process_bus(None);
+
+    match results.pop().unwrap() {
+        ProcessResult::Transaction { transaction } => {
+            assert_eq!(transaction.slave_ack, Some(0xFF));
+            assert_eq!(transaction.reply, None);
+        }
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn broadcast() {
+    let mut d = AutoLoopback::sniffer();
+    let msg = MasterTelegram {
+        telegram: Telegram {
+            src: 0x10,
+            dest: 0xFE,
+            service: 0x0701,
+            data: Buffer::from_slice(&[0x80, 0x0D]),
+        },
+        flags: TelegramFlags::none(),
+    };
+
+    d.send_external_msg(&msg);
+    let results = d.process_bus(None);
+    assert!(results.iter().all(ProcessResult::is_none));
+
+    let mut results = d.process(0xAA, None);
+    match results.pop().unwrap() {
+        ProcessResult::Transaction { transaction } => {
+            assert_eq!(transaction.telegram.dest, 0xFE);
+            assert!(transaction.telegram_crc_ok);
+            assert_eq!(transaction.slave_ack, None);
+        }
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn reply_len_overflow() {
+    let mut d = AutoLoopback::sniffer();
+    let msg = example1();
+
+    d.send_external_msg(&msg);
+    // slave ACK, a reply length over MAX_BUF and more bytes than fit
+    d.send_external_bytes(&[0x00, 0x40]);
+    d.send_external_bytes(&[0x01; 40]);
+    let results = d.process_bus(None);
+
+    let mut transactions = results.iter().filter(|res| !res.is_none());
+    match transactions.next().unwrap() {
+        ProcessResult::Transaction { transaction } => {
+            assert_eq!(transaction.telegram.dest, 0x51);
+            assert_eq!(transaction.slave_ack, Some(0x00));
+            assert_eq!(transaction.reply, None);
+            assert_eq!(transaction.reply_crc_ok, Some(false));
+            assert_eq!(transaction.master_ack, None);
+        }
+        other => panic!("{:?}", other),
+    }
+    assert!(transactions.next().is_none());
+    assert_eq!(d.drain_events(), [BusEvent::ReplyLenOverflow { len: 0x40 }]);
+
+    // nothing left over at the next SYN
+    let results = d.process(0xAA, None);
+    assert!(results.iter().all(ProcessResult::is_none));
+}
@@ -0,0 +1,115 @@
+-- Wireshark dissector for captures written by `ebus::pcap::PcapWriter`.
+--
+-- Decoded telegrams use link type USER0 (147), raw symbols USER1 (148).
+-- Load with `wireshark -X lua_script:wireshark/ebus.lua capture.pcapng` or copy into
+-- the personal Lua plugin folder.
+
+local ebus = Proto("ebus", "eBUS telegram")
+local ebus_raw = Proto("ebus_raw", "eBUS symbols")
+
+local FLAG_TELEGRAM_CRC_OK = 0x01
+local FLAG_SLAVE_ACK = 0x02
+local FLAG_REPLY = 0x04
+local FLAG_REPLY_CRC_OK = 0x08
+local FLAG_MASTER_ACK = 0x10
+
+local ack_names = { [0x00] = "ACK", [0xFF] = "NACK" }
+
+local f = {
+    flags = ProtoField.uint8("ebus.flags", "Flags", base.HEX),
+    telegram_crc_ok = ProtoField.bool("ebus.flags.telegram_crc_ok", "Telegram CRC ok", 8, nil, FLAG_TELEGRAM_CRC_OK),
+    reply_crc_ok = ProtoField.bool("ebus.flags.reply_crc_ok", "Reply CRC ok", 8, nil, FLAG_REPLY_CRC_OK),
+    src = ProtoField.uint8("ebus.src", "Source (QQ)", base.HEX),
+    dest = ProtoField.uint8("ebus.dest", "Destination (ZZ)", base.HEX),
+    service = ProtoField.uint16("ebus.service", "Service (PB SB)", base.HEX),
+    len = ProtoField.uint8("ebus.len", "Length (NN)", base.DEC),
+    data = ProtoField.bytes("ebus.data", "Data"),
+    ack = ProtoField.uint8("ebus.ack", "ACK", base.HEX, ack_names),
+    reply_len = ProtoField.uint8("ebus.reply.len", "Reply length", base.DEC),
+    reply_data = ProtoField.bytes("ebus.reply.data", "Reply data"),
+    master_ack = ProtoField.uint8("ebus.master_ack", "Master ACK", base.HEX, ack_names),
+}
+ebus.fields = f
+
+local symbols = ProtoField.bytes("ebus_raw.symbols", "Symbols", base.SPACE)
+ebus_raw.fields = { symbols }
+
+local e_crc = ProtoExpert.new("ebus.crc_error", "CRC error", expert.group.CHECKSUM, expert.severity.ERROR)
+local e_nack = ProtoExpert.new("ebus.nack", "Not acknowledged", expert.group.RESPONSE_CODE, expert.severity.WARN)
+ebus.experts = { e_crc, e_nack }
+
+local function add_ack(tree, field, tvb, offset)
+    local item = tree:add(field, tvb(offset, 1))
+    if tvb(offset, 1):uint() ~= 0x00 then
+        item:add_proto_expert_info(e_nack)
+    end
+end
+
+function ebus.dissector(tvb, pinfo, root)
+    if tvb:len() < 6 then
+        return 0
+    end
+    pinfo.cols.protocol = "eBUS"
+
+    local flags = tvb(0, 1):uint()
+    local tree = root:add(ebus, tvb())
+    local flags_item = tree:add(f.flags, tvb(0, 1))
+    flags_item:add(f.telegram_crc_ok, tvb(0, 1))
+    if bit.band(flags, FLAG_REPLY) ~= 0 then
+        flags_item:add(f.reply_crc_ok, tvb(0, 1))
+    end
+
+    local src = tvb(1, 1):uint()
+    local dest = tvb(2, 1):uint()
+    local len = tvb(5, 1):uint()
+    tree:add(f.src, tvb(1, 1))
+    tree:add(f.dest, tvb(2, 1))
+    tree:add(f.service, tvb(3, 2))
+    tree:add(f.len, tvb(5, 1))
+    local offset = 6
+    if len > 0 then
+        tree:add(f.data, tvb(offset, len))
+    end
+    offset = offset + len
+    pinfo.cols.src = string.format("%02X", src)
+    pinfo.cols.dst = string.format("%02X", dest)
+    local info = string.format("%02X -> %02X %04X", src, dest, tvb(3, 2):uint())
+    if bit.band(flags, FLAG_TELEGRAM_CRC_OK) == 0 then
+        tree:add_proto_expert_info(e_crc, "Telegram CRC error")
+        info = info .. " [CRC error]"
+    end
+
+    if bit.band(flags, FLAG_SLAVE_ACK) ~= 0 then
+        add_ack(tree, f.ack, tvb, offset)
+        offset = offset + 1
+    end
+    if bit.band(flags, FLAG_REPLY) ~= 0 then
+        local reply_len = tvb(offset, 1):uint()
+        tree:add(f.reply_len, tvb(offset, 1))
+        if reply_len > 0 then
+            tree:add(f.reply_data, tvb(offset + 1, reply_len))
+            info = info .. " reply " .. tvb(offset + 1, reply_len):bytes():tohex()
+        end
+        offset = offset + 1 + reply_len
+        if bit.band(flags, FLAG_REPLY_CRC_OK) == 0 then
+            tree:add_proto_expert_info(e_crc, "Reply CRC error")
+        end
+    end
+    if bit.band(flags, FLAG_MASTER_ACK) ~= 0 then
+        add_ack(tree, f.master_ack, tvb, offset)
+    end
+
+    pinfo.cols.info = info
+    return tvb:len()
+end
+
+function ebus_raw.dissector(tvb, pinfo, root)
+    pinfo.cols.protocol = "eBUS raw"
+    root:add(ebus_raw, tvb()):add(symbols, tvb())
+    pinfo.cols.info = tvb():bytes():tohex()
+    return tvb:len()
+end
+
+local encap = DissectorTable.get("wtap_encap")
+encap:add(wtap.USER0, ebus)
+encap:add(wtap.USER1, ebus_raw)